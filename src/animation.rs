@@ -0,0 +1,82 @@
+// Playing a glTF model's animation clips by name.
+//
+// The glTF loader gives the model's root entity an AnimationSet,
+//  with its clips keyed by their index in the glTF file;
+//  an AnimationController refers to them by name instead.
+
+use std::collections::HashMap;
+
+use amethyst::ecs::{Component, DenseVecStorage};
+
+// The tank's gun recoiling, played when it fires.
+pub const RECOIL_CLIP: &str = "Recoil";
+
+// A change to a clip's playback, applied by the AnimationControllerSystem.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnimationRequest {
+    /// Play the clip from the start (restarting it if it's playing)
+    Play { clip: String, looping: bool, rate: f32 },
+    Stop { clip: String },
+    /// Set how much the clip contributes, when blended with other clips
+    ///  animating the same nodes (0.0 to 1.0)
+    Blend { clip: String, weight: f32 },
+}
+
+// Plays, loops, blends and stops the animation clips of
+//  the glTF model on the same entity.
+//
+// Requests made before the model (and its animations) have loaded
+//  are applied once it has.
+#[derive(Clone, Debug, Default)]
+pub struct AnimationController {
+    /// The glTF animation index of each clip, by name
+    pub clips: HashMap<String, usize>,
+    /// Requests which haven't been applied yet, oldest first
+    pub pending: Vec<AnimationRequest>,
+}
+
+impl AnimationController {
+    pub fn new(clips: HashMap<String, usize>) -> Self {
+        AnimationController {
+            clips,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn has_clip(&self, clip: &str) -> bool {
+        self.clips.contains_key(clip)
+    }
+
+    // Play a clip once.
+    pub fn play(&mut self, clip: &str) -> () {
+        self.pending.push(AnimationRequest::Play {
+            clip: clip.to_string(),
+            looping: false,
+            rate: 1.0,
+        });
+    }
+
+    // Play a clip repeatedly, until it's stopped.
+    pub fn play_looping(&mut self, clip: &str, rate: f32) -> () {
+        self.pending.push(AnimationRequest::Play {
+            clip: clip.to_string(),
+            looping: true,
+            rate,
+        });
+    }
+
+    pub fn stop(&mut self, clip: &str) -> () {
+        self.pending.push(AnimationRequest::Stop { clip: clip.to_string() });
+    }
+
+    pub fn blend(&mut self, clip: &str, weight: f32) -> () {
+        self.pending.push(AnimationRequest::Blend {
+            clip: clip.to_string(),
+            weight: weight.max(0.0).min(1.0),
+        });
+    }
+}
+
+impl Component for AnimationController {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -1,11 +1,25 @@
+// The game's assets, listed by name in a manifest (`assets/manifest.ron`)
+//  and loaded into an AssetRegistry as each level needs them.
+//
+// Adding a mesh, texture or map only needs an entry in the manifest;
+//  code and data files refer to assets by their names.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
 use amethyst;
 
 use amethyst::{
     assets::{AssetStorage, Handle, Loader, ProgressCounter},
+    error::Error,
     prelude::*,
     renderer::{
         sprite::SpriteSheetHandle,
         formats::texture::ImageFormat,
+        loaders::load_from_srgba,
+        palette::Srgba,
+        types::TextureData,
         Material,
         MaterialDefaults,
         Mesh,
@@ -19,116 +33,491 @@ use amethyst_gltf::{
     GltfSceneFormat,
     GltfSceneOptions,
 };
+use log::warn;
+use ron::de::from_reader as from_ron_reader;
+use serde::{Deserialize, Serialize};
 
+use crate::level::Level;
 use crate::sprite_grid::{
     SpriteGridFormat,
 };
 
-static ASSET_MESH_TANK_GLTF: &str = "mesh/tank.gltf";
-static ASSET_TEXTURE_TANK_BLUE: &str = "mesh/tank_texture_blue.png";
-static ASSET_TEXTURE_MAP_TILES: &str = "texture/basic_map_tiles.png";
-static ASSET_SPRITESHEET_MAP_TILES: &str = "texture/basic_map_spritesheet.ron";
-static ASSET_RON_BASIC_MAP: &str = "texture/basic_map.ron";
+// Where a material's texture comes from.
+#[derive(Clone, Debug, Deserialize)]
+pub enum TextureSource {
+    /// A texture from the manifest, by name
+    Texture(String),
+    /// A plain colour, as (red, green, blue, alpha)
+    Colour((f32, f32, f32, f32)),
+}
 
-#[derive(Clone)]
-pub struct Assets {
-    pub tank_gltf: Handle<GltfSceneAsset>,
-    pub tank_blue_material: Handle<Material>,
-    pub map_sprite_sheet_material: Handle<Material>,
-    pub map_sprite_sheet: SpriteSheetHandle,
-    pub sprite_grid: Handle<Mesh>,
-}
-
-pub fn load_assets(world: &mut World, progress: &mut ProgressCounter) -> () {
-    let assets = {
-        let mesh_storage = world.read_resource();
-        let texture_storage = world.read_resource::<AssetStorage<Texture>>();
-        let material_storage = world.read_resource::<AssetStorage<Material>>();
-        let gltf_prefab_storage = world.write_resource();
-        let sprite_sheet_storage =
-            world.read_resource::<AssetStorage<SpriteSheet>>();
-        let material_defaults = world.read_resource::<MaterialDefaults>();
-        let loader = world.read_resource::<Loader>();
-
-        let tank_gltf = {
-            let pc: &mut ProgressCounter = progress;
-            loader.load(
-                ASSET_MESH_TANK_GLTF,
-                GltfSceneFormat(GltfSceneOptions::default()),
-                pc,
-                &gltf_prefab_storage,
-            )
-        };
+#[derive(Clone, Debug, Deserialize)]
+pub struct MaterialDefinition {
+    pub albedo: TextureSource,
+    #[serde(default)]
+    pub emission: Option<TextureSource>,
+}
 
-        let tank_blue_texture = {
-            let pc: &mut ProgressCounter = progress;
-            loader.load(
-                ASSET_TEXTURE_TANK_BLUE,
-                ImageFormat::default(),
-                pc,
-                &texture_storage,
-            )
-        };
-        let tank_blue_material = {
-            let pc: &mut ProgressCounter = progress;
-            loader.load_from_data(
-                Material {
-                    albedo: tank_blue_texture.clone(),
-                    ..material_defaults.0.clone()
-                },
-                pc,
-                &material_storage,
-            )
-        };
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpriteSheetDefinition {
+    /// Path of the sprite sheet's RON file
+    pub path: String,
+    /// Name of the sprite sheet's texture
+    pub texture: String,
+}
 
-        let map_sprite_sheet_texture = {
-            let pc: &mut ProgressCounter = progress;
-            loader.load(
-                ASSET_TEXTURE_MAP_TILES,
-                ImageFormat::default(),
-                pc,
-                &texture_storage,
-            )
-        };
-        let map_sprite_sheet_material = {
-            let pc: &mut ProgressCounter = progress;
-            loader.load_from_data(
-                Material {
-                    albedo: map_sprite_sheet_texture.clone(),
-                    ..material_defaults.0.clone()
-                },
-                pc,
-                &material_storage,
-            )
-        };
+#[derive(Clone, Debug, Deserialize)]
+pub struct GridDefinition {
+    /// Path of the SpriteGrid's RON file
+    pub path: String,
+    /// Name of the texture of the grid's sprite sheet
+    pub texture: String,
+    /// Name of the material to draw the grid with
+    pub material: String,
+}
+
+// Every asset the game can load, by name.
+//
+// Paths are relative to the assets directory.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct AssetManifest {
+    /// glTF scenes
+    #[serde(default)]
+    pub meshes: BTreeMap<String, String>,
+    #[serde(default)]
+    pub textures: BTreeMap<String, String>,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDefinition>,
+    #[serde(default)]
+    pub sprite_sheets: BTreeMap<String, SpriteSheetDefinition>,
+    /// SpriteGrid maps
+    #[serde(default)]
+    pub grids: BTreeMap<String, GridDefinition>,
+    /// The assets directory, which the manifest was loaded from
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+impl AssetManifest {
+    // Load a manifest, checking that the assets it refers to by name exist.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<AssetManifest, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|_| {
+            Error::from_string(format!("Failed to open asset manifest {}", path.display()))
+        })?;
+        let mut manifest: AssetManifest = from_ron_reader(file).map_err(|err| {
+            Error::from_string(format!(
+                "Failed to parse asset manifest {}: {}", path.display(), err
+            ))
+        })?;
+
+        manifest.check_references().map_err(|err| {
+            Error::from_string(format!("Invalid asset manifest {}: {}", path.display(), err))
+        })?;
+        manifest.dir = path.parent().map_or_else(PathBuf::new, Path::to_path_buf);
+        Ok(manifest)
+    }
+
+    // The path of a mesh's glTF file.
+    pub fn mesh_path(&self, mesh: &str) -> Result<PathBuf, Error> {
+        let path = self.meshes.get(mesh).ok_or_else(|| {
+            Error::from_string(format!("unknown mesh '{}'", mesh))
+        })?;
+        Ok(self.dir.join(path))
+    }
 
-        let map_sprite_sheet = {
-            let pc: &mut ProgressCounter = progress;
-            loader.load(
-                ASSET_SPRITESHEET_MAP_TILES,
-                SpriteSheetFormat(map_sprite_sheet_texture.clone()),
-                pc,
-                &sprite_sheet_storage,
-            )
+    // The names of a mesh's animation clips, with their indices in the glTF file
+    //  (which the mesh's AnimationSet is keyed by).
+    //
+    // Unnamed clips are named by their index.
+    pub fn animation_clips(&self, mesh: &str) -> Result<HashMap<String, usize>, Error> {
+        let path = self.mesh_path(mesh)?;
+        let gltf = gltf::Gltf::open(&path).map_err(|err| {
+            Error::from_string(format!("Failed to read glTF file {}: {}", path.display(), err))
+        })?;
+        Ok(gltf
+            .animations()
+            .map(|animation| {
+                let name = animation
+                    .name()
+                    .map_or_else(|| animation.index().to_string(), String::from);
+                (name, animation.index())
+            })
+            .collect())
+    }
+
+    fn check_references(&self) -> Result<(), String> {
+        let check_texture = |owner: &str, texture: &str| {
+            if self.textures.contains_key(texture) {
+                Ok(())
+            } else {
+                Err(format!("{} refers to unknown texture '{}'", owner, texture))
+            }
         };
-        let sprite_grid = {
-            let pc: &mut ProgressCounter = progress;
-            loader.load(
-                ASSET_RON_BASIC_MAP,
-                SpriteGridFormat { texture: map_sprite_sheet_texture.clone() },
-                pc,
-                &mesh_storage
-            )
+
+        for (name, material) in &self.materials {
+            let sources = Some(&material.albedo).into_iter().chain(material.emission.as_ref());
+            for source in sources {
+                if let TextureSource::Texture(texture) = source {
+                    check_texture(&format!("material '{}'", name), texture)?;
+                }
+            }
+        }
+        for (name, sprite_sheet) in &self.sprite_sheets {
+            check_texture(&format!("sprite sheet '{}'", name), &sprite_sheet.texture)?;
+        }
+        for (name, grid) in &self.grids {
+            check_texture(&format!("grid '{}'", name), &grid.texture)?;
+            if !self.materials.contains_key(&grid.material) {
+                return Err(format!(
+                    "grid '{}' refers to unknown material '{}'", name, grid.material
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+// A SpriteGrid map's mesh, and the material to draw it with.
+#[derive(Clone)]
+pub struct GridAsset {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<Material>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AssetKind {
+    // In the order they must be loaded, since e.g. materials refer to textures.
+    Mesh,
+    Texture,
+    Material,
+    SpriteSheet,
+    Grid,
+}
+
+// Identifies an asset in the manifest.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AssetKey {
+    pub kind: AssetKind,
+    pub name: String,
+}
+
+impl AssetKey {
+    pub fn new<S: Into<String>>(kind: AssetKind, name: S) -> Self {
+        AssetKey { kind, name: name.into() }
+    }
+}
+
+// The assets something (e.g. a level) needs, by name.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AssetDependencies {
+    #[serde(default)]
+    pub meshes: Vec<String>,
+    #[serde(default)]
+    pub textures: Vec<String>,
+    #[serde(default)]
+    pub materials: Vec<String>,
+    #[serde(default)]
+    pub sprite_sheets: Vec<String>,
+    #[serde(default)]
+    pub grids: Vec<String>,
+}
+
+impl AssetDependencies {
+    pub fn extend(&mut self, other: &AssetDependencies) -> () {
+        self.meshes.extend(other.meshes.iter().cloned());
+        self.textures.extend(other.textures.iter().cloned());
+        self.materials.extend(other.materials.iter().cloned());
+        self.sprite_sheets.extend(other.sprite_sheets.iter().cloned());
+        self.grids.extend(other.grids.iter().cloned());
+    }
+}
+
+impl AssetManifest {
+    // Every asset needed for the dependencies,
+    //  including the textures and materials they refer to.
+    //
+    // Iterating over the set gives the order to load them in.
+    pub fn resolve(
+        &self,
+        dependencies: &AssetDependencies,
+    ) -> Result<BTreeSet<AssetKey>, String> {
+        let mut keys = BTreeSet::new();
+        let mut add = |kind: AssetKind, names: &Vec<String>| {
+            for name in names {
+                keys.insert(AssetKey::new(kind, name.as_str()));
+            }
         };
+        add(AssetKind::Mesh, &dependencies.meshes);
+        add(AssetKind::Texture, &dependencies.textures);
+        add(AssetKind::Material, &dependencies.materials);
+        add(AssetKind::SpriteSheet, &dependencies.sprite_sheets);
+        add(AssetKind::Grid, &dependencies.grids);
 
-        Assets {
-            tank_gltf,
-            tank_blue_material,
-            map_sprite_sheet_material,
-            map_sprite_sheet,
-            sprite_grid,
+        let mut resolved = BTreeSet::new();
+        for key in keys {
+            self.add_with_references(key, &mut resolved)?;
         }
-    };
+        Ok(resolved)
+    }
+
+    fn add_with_references(
+        &self,
+        key: AssetKey,
+        resolved: &mut BTreeSet<AssetKey>,
+    ) -> Result<(), String> {
+        let unknown = || format!("unknown {:?} '{}'", key.kind, key.name);
+        let texture_key = |name: &String| AssetKey::new(AssetKind::Texture, name.as_str());
+
+        match key.kind {
+            AssetKind::Mesh => {
+                self.meshes.get(&key.name).ok_or_else(unknown)?;
+            }
+            AssetKind::Texture => {
+                self.textures.get(&key.name).ok_or_else(unknown)?;
+            }
+            AssetKind::Material => {
+                let material = self.materials.get(&key.name).ok_or_else(unknown)?;
+                let sources = Some(&material.albedo).into_iter().chain(material.emission.as_ref());
+                for source in sources {
+                    if let TextureSource::Texture(texture) = source {
+                        resolved.insert(texture_key(texture));
+                    }
+                }
+            }
+            AssetKind::SpriteSheet => {
+                let sprite_sheet = self.sprite_sheets.get(&key.name).ok_or_else(unknown)?;
+                resolved.insert(texture_key(&sprite_sheet.texture));
+            }
+            AssetKind::Grid => {
+                let grid = self.grids.get(&key.name).ok_or_else(unknown)?;
+                resolved.insert(texture_key(&grid.texture));
+                self.add_with_references(
+                    AssetKey::new(AssetKind::Material, grid.material.as_str()),
+                    resolved,
+                )?;
+            }
+        }
+        resolved.insert(key);
+        Ok(())
+    }
+}
+
+// Handles of the loaded assets, by their names in the manifest.
+//
+// Assets are reference-counted, so that e.g. assets shared by consecutive levels
+//  stay loaded; an asset is released when its count drops to zero.
+#[derive(Clone, Default)]
+pub struct AssetRegistry {
+    meshes: HashMap<String, Handle<GltfSceneAsset>>,
+    textures: HashMap<String, Handle<Texture>>,
+    materials: HashMap<String, Handle<Material>>,
+    sprite_sheets: HashMap<String, SpriteSheetHandle>,
+    grids: HashMap<String, GridAsset>,
+    /// Each mesh's animation clips, by name
+    animation_clips: HashMap<String, HashMap<String, usize>>,
+    ref_counts: HashMap<AssetKey, usize>,
+    /// The assets acquired for the current level
+    level_assets: BTreeSet<AssetKey>,
+}
+
+impl AssetRegistry {
+    pub fn mesh(&self, name: &str) -> Option<Handle<GltfSceneAsset>> {
+        self.meshes.get(name).cloned()
+    }
+
+    // The mesh's animation clips, by name; empty if it has none (or isn't loaded).
+    pub fn animation_clips(&self, mesh: &str) -> HashMap<String, usize> {
+        self.animation_clips.get(mesh).cloned().unwrap_or_default()
+    }
+
+    pub fn texture(&self, name: &str) -> Option<Handle<Texture>> {
+        self.textures.get(name).cloned()
+    }
+
+    pub fn material(&self, name: &str) -> Option<Handle<Material>> {
+        self.materials.get(name).cloned()
+    }
+
+    pub fn sprite_sheet(&self, name: &str) -> Option<SpriteSheetHandle> {
+        self.sprite_sheets.get(name).cloned()
+    }
+
+    pub fn grid(&self, name: &str) -> Option<GridAsset> {
+        self.grids.get(name).cloned()
+    }
+
+    // Register an already loaded material, for tests which don't run the Loader.
+    #[cfg(test)]
+    pub fn insert_material(&mut self, name: &str, material: Handle<Material>) -> () {
+        self.materials.insert(name.to_string(), material);
+    }
+
+    // Drop the registry's handle of an asset;
+    //  the asset is unloaded once nothing else holds a handle to it.
+    fn remove(&mut self, key: &AssetKey) -> () {
+        match key.kind {
+            AssetKind::Mesh => {
+                self.meshes.remove(&key.name);
+                self.animation_clips.remove(&key.name);
+            }
+            AssetKind::Texture => { self.textures.remove(&key.name); }
+            AssetKind::Material => { self.materials.remove(&key.name); }
+            AssetKind::SpriteSheet => { self.sprite_sheets.remove(&key.name); }
+            AssetKind::Grid => { self.grids.remove(&key.name); }
+        }
+    }
+
+    pub fn release(&mut self, keys: &BTreeSet<AssetKey>) -> () {
+        for key in keys {
+            let count = self.ref_counts.get(key).cloned().unwrap_or(0);
+            if count <= 1 {
+                self.ref_counts.remove(key);
+                self.remove(key);
+            } else {
+                self.ref_counts.insert(key.clone(), count - 1);
+            }
+        }
+    }
+}
+
+// Acquire assets from the manifest, loading the ones which aren't loaded yet.
+//
+// Only newly loaded assets are counted by the `progress`.
+pub fn acquire_assets(
+    world: &mut World,
+    keys: &BTreeSet<AssetKey>,
+    progress: &mut ProgressCounter
+) -> () {
+    world.entry::<AssetRegistry>().or_insert_with(AssetRegistry::default);
+
+    let manifest = world.read_resource::<AssetManifest>();
+    let mut registry = world.write_resource::<AssetRegistry>();
+    let mesh_storage = world.read_resource();
+    let texture_storage = world.read_resource::<AssetStorage<Texture>>();
+    let material_storage = world.read_resource::<AssetStorage<Material>>();
+    let gltf_prefab_storage = world.write_resource();
+    let sprite_sheet_storage =
+        world.read_resource::<AssetStorage<SpriteSheet>>();
+    let material_defaults = world.read_resource::<MaterialDefaults>();
+    let loader = world.read_resource::<Loader>();
+
+    // Keys are ordered so that textures (and materials) are loaded
+    //  before the assets which refer to them.
+    for key in keys {
+        *registry.ref_counts.entry(key.clone()).or_insert(0) += 1;
+        if registry.ref_counts[key] > 1 {
+            continue;
+        }
+
+        let name = key.name.clone();
+        // References were checked when the manifest was loaded.
+        match key.kind {
+            AssetKind::Mesh => {
+                let pc: &mut ProgressCounter = progress;
+                let options = GltfSceneOptions {
+                    load_animations: true,
+                    ..GltfSceneOptions::default()
+                };
+                let mesh = loader.load(
+                    manifest.meshes[&name].as_str(),
+                    GltfSceneFormat(options),
+                    pc,
+                    &gltf_prefab_storage,
+                );
+                let animation_clips = manifest.animation_clips(&name).unwrap_or_else(|err| {
+                    warn!("No animations for mesh '{}': {}", name, err);
+                    HashMap::new()
+                });
+                registry.meshes.insert(name.clone(), mesh);
+                registry.animation_clips.insert(name, animation_clips);
+            }
+            AssetKind::Texture => {
+                let pc: &mut ProgressCounter = progress;
+                let texture = loader.load(
+                    manifest.textures[&name].as_str(),
+                    ImageFormat::default(),
+                    pc,
+                    &texture_storage,
+                );
+                registry.textures.insert(name, texture);
+            }
+            AssetKind::Material => {
+                let definition = &manifest.materials[&name];
+                let mut texture_from = |source: &TextureSource| match source {
+                    TextureSource::Texture(texture) => registry.textures[texture].clone(),
+                    TextureSource::Colour((r, g, b, a)) => {
+                        let pc: &mut ProgressCounter = progress;
+                        loader.load_from_data(
+                            TextureData::from(load_from_srgba(Srgba::new(*r, *g, *b, *a))),
+                            pc,
+                            &texture_storage,
+                        )
+                    }
+                };
+                let albedo = texture_from(&definition.albedo);
+                let emission = definition.emission.as_ref().map(&mut texture_from);
+
+                let pc: &mut ProgressCounter = progress;
+                let material = loader.load_from_data(
+                    Material {
+                        albedo,
+                        emission: emission.unwrap_or_else(|| material_defaults.0.emission.clone()),
+                        ..material_defaults.0.clone()
+                    },
+                    pc,
+                    &material_storage,
+                );
+                registry.materials.insert(name, material);
+            }
+            AssetKind::SpriteSheet => {
+                let definition = &manifest.sprite_sheets[&name];
+                let pc: &mut ProgressCounter = progress;
+                let sprite_sheet = loader.load(
+                    definition.path.as_str(),
+                    SpriteSheetFormat(registry.textures[&definition.texture].clone()),
+                    pc,
+                    &sprite_sheet_storage,
+                );
+                registry.sprite_sheets.insert(name, sprite_sheet);
+            }
+            AssetKind::Grid => {
+                let definition = &manifest.grids[&name];
+                let pc: &mut ProgressCounter = progress;
+                let mesh = loader.load(
+                    definition.path.as_str(),
+                    SpriteGridFormat { texture: registry.textures[&definition.texture].clone() },
+                    pc,
+                    &mesh_storage
+                );
+                let grid = GridAsset {
+                    mesh,
+                    material: registry.materials[&definition.material].clone(),
+                };
+                registry.grids.insert(name, grid);
+            }
+        }
+    }
+}
+
+// Acquire the level's assets, and then release the previous level's;
+//  so assets used by both stay loaded.
+pub fn load_level_assets(
+    world: &mut World,
+    level: &Level,
+    progress: &mut ProgressCounter
+) -> () {
+    acquire_assets(world, &level.assets, progress);
+
+    let mut registry = world.write_resource::<AssetRegistry>();
+    let previous = std::mem::replace(&mut registry.level_assets, level.assets.clone());
+    registry.release(&previous);
+}
 
-    world.insert(assets);
+// Release the current level's assets (e.g. when returning to the main menu).
+pub fn unload_level_assets(world: &mut World) -> () {
+    if let Some(mut registry) = world.try_fetch_mut::<AssetRegistry>() {
+        let previous = std::mem::replace(&mut registry.level_assets, BTreeSet::new());
+        registry.release(&previous);
+    }
 }
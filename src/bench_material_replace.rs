@@ -0,0 +1,257 @@
+// Benchmarks the ReplaceMaterialSystem with hundreds of tanks,
+//  each a hierarchy of nodes like a glTF scene's, in a headless World.
+//
+// Times matching newly spawned tanks, frames where nothing changes,
+//  frames with hit flashes, and frames where nodes are re-parented between tanks.
+// Then checks that every node ended up with the expected material,
+//  exiting with a non-zero status if one didn't.
+
+// The game's modules are shared with the game binaries,
+//  but only the material replacement is used here.
+#![allow(dead_code)]
+
+use std::time::{Duration, Instant};
+
+use amethyst::{
+    assets::{AssetStorage, Handle},
+    core::{timing::Time, Named, Parent, ParentHierarchy, SystemDesc},
+    ecs::{prelude::Entity, RunNow},
+    prelude::*,
+    renderer::Material,
+};
+
+use crate::replace_material::{MaterialOverride, MaterialRule, NamePattern, ReplaceMaterial};
+use crate::systems::ReplaceMaterialSystemDesc;
+
+mod animation;
+mod assets;
+mod camera;
+mod collision;
+mod controls;
+mod events;
+mod key_bindings;
+mod level;
+mod logging;
+mod options;
+mod player;
+mod preferences;
+mod projectile;
+mod replace_material;
+mod spatial_grid;
+mod sprite_grid;
+mod states;
+mod systems;
+mod team;
+mod turret;
+mod unit;
+mod utils;
+
+const USAGE: &str = "\
+Usage: bench-material-replace [options]
+
+Options:
+    --tanks <n>         Number of tanks (default: 500)
+    --nodes <n>         Number of nodes in each tank (default: 20)
+    --frames <n>        Number of frames of each kind (default: 100)
+    --help              Print this message";
+
+const FRAME_TIME: f32 = 1.0 / 60.0;
+// Portion of the tanks which are hit each frame, in the frames with hit flashes.
+const HIT_PORTION: f32 = 0.1;
+const HIT_FLASH_DURATION: f32 = 0.1;
+
+// Deterministic pseudo-random numbers (xorshift),
+//  so that runs are comparable.
+struct Random(u32);
+
+impl Random {
+    // A value in 0..count.
+    fn index(&mut self, count: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as usize % count
+    }
+}
+
+// A tank, and the nodes in its hierarchy.
+struct Tank {
+    entity: Entity,
+    /// The material its rule gives "body" nodes
+    paint: Handle<Material>,
+    nodes: Vec<Entity>,
+}
+
+fn node_name(index: usize) -> String {
+    if index % 2 == 0 {
+        format!("body_{}", index)
+    } else {
+        format!("track_{}", index)
+    }
+}
+
+// Spawn a tank whose nodes form a tree, with three children per node.
+fn spawn_tank(
+    world: &mut World,
+    paint: &Handle<Material>,
+    original: &Handle<Material>,
+    num_nodes: usize,
+) -> Tank {
+    let rule = MaterialRule {
+        name: Some(NamePattern::Glob(glob::Pattern::new("body_*").expect("valid pattern"))),
+        original_nodes: None,
+        replacement: Some(paint.clone()),
+        tint: None,
+    };
+    let entity = world.create_entity().with(ReplaceMaterial::new(vec![rule])).build();
+
+    let mut nodes: Vec<Entity> = Vec::with_capacity(num_nodes);
+    for index in 0..num_nodes {
+        let parent = if index == 0 { entity } else { nodes[(index - 1) / 3] };
+        let node = world
+            .create_entity()
+            .with(Parent { entity: parent })
+            .with(Named::new(node_name(index)))
+            .with(original.clone())
+            .build();
+        nodes.push(node);
+    }
+
+    Tank { entity, paint: paint.clone(), nodes }
+}
+
+fn run_frame<S: for<'a> RunNow<'a>>(world: &mut World, system: &mut S) -> () {
+    system.run_now(world);
+    world.maintain();
+}
+
+fn report(name: &str, elapsed: Duration, frames: usize) -> () {
+    println!(
+        "{:<10} {:>10.3?} in all   {:>10.3?} per frame",
+        name,
+        elapsed,
+        elapsed / frames.max(1) as u32,
+    );
+}
+
+fn parse_count(value: Option<String>, option: &str) -> usize {
+    value.and_then(|value| value.parse().ok()).unwrap_or_else(|| {
+        eprintln!("{} needs a number\n\n{}", option, USAGE);
+        std::process::exit(2);
+    })
+}
+
+fn main() -> () {
+    let mut num_tanks = 500;
+    let mut num_nodes = 20;
+    let mut num_frames = 100;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--tanks" => num_tanks = parse_count(args.next(), "--tanks").max(1),
+            "--nodes" => num_nodes = parse_count(args.next(), "--nodes").max(1),
+            "--frames" => num_frames = parse_count(args.next(), "--frames"),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            other => {
+                eprintln!("unknown option '{}'\n\n{}", other, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let mut world = World::new();
+    world.register::<Named>();
+    let mut system = ReplaceMaterialSystemDesc::default().build(&mut world);
+    // Nodes are found from the Parent storage's events,
+    //  so the hierarchy itself isn't maintained here.
+    let hierarchy_events = world.write_storage::<Parent>().register_reader();
+    world.insert(ParentHierarchy::new(hierarchy_events));
+    world.insert(AssetStorage::<Material>::default());
+    world.write_resource::<Time>().set_delta_seconds(FRAME_TIME);
+
+    let (original, flash, paints) = {
+        let storage = world.read_resource::<AssetStorage<Material>>();
+        (storage.allocate(), storage.allocate(), vec![storage.allocate(), storage.allocate()])
+    };
+    println!(
+        "{} tanks of {} nodes, {} frames of each kind",
+        num_tanks, num_nodes, num_frames,
+    );
+
+    let start = Instant::now();
+    let mut tanks: Vec<Tank> = (0..num_tanks)
+        .map(|index| spawn_tank(&mut world, &paints[index % paints.len()], &original, num_nodes))
+        .collect();
+    run_frame(&mut world, &mut system);
+    report("spawn", start.elapsed(), 1);
+
+    let start = Instant::now();
+    for _ in 0..num_frames {
+        run_frame(&mut world, &mut system);
+    }
+    report("idle", start.elapsed(), num_frames);
+
+    let mut random = Random(0x9e37_79b9);
+    let hits_per_frame = ((num_tanks as f32 * HIT_PORTION) as usize).max(1);
+    let start = Instant::now();
+    for _ in 0..num_frames {
+        {
+            let mut replacements = world.write_storage::<ReplaceMaterial>();
+            for _ in 0..hits_per_frame {
+                let tank = &tanks[random.index(tanks.len())];
+                if let Some(replacement) = replacements.get_mut(tank.entity) {
+                    replacement.push_override(
+                        MaterialOverride::new("hit_flash", 100)
+                            .with_material(flash.clone())
+                            .with_duration(HIT_FLASH_DURATION),
+                    );
+                }
+            }
+        }
+        run_frame(&mut world, &mut system);
+    }
+    report("flash", start.elapsed(), num_frames);
+
+    // Move a leaf node (the last of a tank's nodes) to another tank each frame.
+    let start = Instant::now();
+    for _ in 0..num_frames {
+        let from = random.index(tanks.len());
+        let to = random.index(tanks.len());
+        if tanks[from].nodes.len() > 1 {
+            let node = tanks[from].nodes.pop().expect("tank has nodes");
+            world
+                .write_storage::<Parent>()
+                .insert(node, Parent { entity: tanks[to].entity })
+                .expect("node is alive");
+            tanks[to].nodes.push(node);
+        }
+        run_frame(&mut world, &mut system);
+    }
+    report("reparent", start.elapsed(), num_frames);
+
+    // Let the last hit flashes expire.
+    let frames_to_expire = (HIT_FLASH_DURATION / FRAME_TIME).ceil() as usize + 1;
+    for _ in 0..frames_to_expire {
+        run_frame(&mut world, &mut system);
+    }
+
+    let materials = world.read_storage::<Handle<Material>>();
+    let names = world.read_storage::<Named>();
+    let mut mismatches = 0;
+    for tank in &tanks {
+        for node in &tank.nodes {
+            let is_body = names.get(*node).map_or(false, |named| named.name.starts_with("body_"));
+            let expected = if is_body { &tank.paint } else { &original };
+            if materials.get(*node).map(Handle::id) != Some(expected.id()) {
+                mismatches += 1;
+            }
+        }
+    }
+    if mismatches > 0 {
+        println!("{} nodes have the wrong material", mismatches);
+        std::process::exit(1);
+    }
+}
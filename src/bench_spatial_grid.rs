@@ -0,0 +1,210 @@
+// Benchmarks the SpatialGrid's queries against brute force
+//  (checking every entity), for randomly placed entities.
+//
+// Also checks that both give the same results,
+//  exiting with a non-zero status if they don't.
+
+// The game's modules are shared with the game binaries,
+//  but only the SpatialGrid itself is used here.
+#![allow(dead_code)]
+
+use std::cmp::Ordering;
+use std::time::{Duration, Instant};
+
+use amethyst::{
+    ecs::prelude::Entity,
+    prelude::*,
+};
+use nalgebra::Vector2;
+
+use crate::spatial_grid::{RayHit, SpatialGrid};
+use crate::sprite_grid::TILE_SIZE;
+
+mod spatial_grid;
+mod sprite_grid;
+
+const USAGE: &str = "\
+Usage: bench-spatial-grid [options]
+
+Options:
+    --entities <n>      Number of entities (default: 1000)
+    --queries <n>       Number of each kind of query (default: 1000)
+    --map-size <n>      Width of the (square) map, in tiles (default: 16)
+    --help              Print this message";
+
+// Query sizes, in tiles.
+const QUERY_RADIUS: f32 = 1.5;
+const QUERY_RECT_SIZE: f32 = 2.0;
+const RAY_LENGTH: f32 = 6.0;
+const RAY_RADIUS: f32 = 0.5;
+
+// Deterministic pseudo-random numbers (xorshift),
+//  so that runs are comparable.
+struct Random(u32);
+
+impl Random {
+    // A value in 0.0..1.0.
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0 as f32 / u32::MAX as f32
+    }
+
+    fn position(&mut self, half_width: f32) -> Vector2<f32> {
+        Vector2::new(
+            (self.next() * 2.0 - 1.0) * half_width,
+            (self.next() * 2.0 - 1.0) * half_width,
+        )
+    }
+}
+
+// Time a query over each of the inputs, returning its results.
+fn time<I, T, F: FnMut(&I) -> T>(inputs: &[I], mut query: F) -> (Duration, Vec<T>) {
+    let start = Instant::now();
+    let results = inputs.iter().map(|input| query(input)).collect();
+    (start.elapsed(), results)
+}
+
+// Results compared by entity, ignoring order.
+fn sorted(mut entities: Vec<Entity>) -> Vec<Entity> {
+    entities.sort_by_key(|entity| entity.id());
+    entities
+}
+
+fn report(name: &str, grid: Duration, brute_force: Duration, matches: bool) -> bool {
+    let speedup = brute_force.as_secs_f64() / grid.as_secs_f64().max(f64::EPSILON);
+    println!(
+        "{:<8} grid {:>10.3?}   brute force {:>10.3?}   {:>6.1}x{}",
+        name,
+        grid,
+        brute_force,
+        speedup,
+        if matches { "" } else { "   RESULTS DIFFER" },
+    );
+    matches
+}
+
+fn parse_count(value: Option<String>, option: &str) -> usize {
+    value.and_then(|value| value.parse().ok()).unwrap_or_else(|| {
+        eprintln!("{} needs a number\n\n{}", option, USAGE);
+        std::process::exit(2);
+    })
+}
+
+fn main() -> () {
+    let mut num_entities = 1000;
+    let mut num_queries = 1000;
+    let mut map_size = 16;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--entities" => num_entities = parse_count(args.next(), "--entities"),
+            "--queries" => num_queries = parse_count(args.next(), "--queries"),
+            "--map-size" => map_size = parse_count(args.next(), "--map-size"),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            other => {
+                eprintln!("unknown option '{}'\n\n{}", other, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let half_width = TILE_SIZE * map_size as f32 / 2.0;
+    let mut random = Random(0x9e37_79b9);
+    let mut world = World::new();
+    let placed: Vec<(Entity, Vector2<f32>)> = (0..num_entities)
+        .map(|_| (world.create_entity().build(), random.position(half_width)))
+        .collect();
+
+    let mut grid = SpatialGrid::new(Vector2::new(-half_width, -half_width), TILE_SIZE);
+    let start = Instant::now();
+    for (entity, position) in &placed {
+        grid.insert(*entity, *position);
+    }
+    println!(
+        "{} entities on a {}x{} tile map, {} queries of each kind",
+        grid.len(), map_size, map_size, num_queries,
+    );
+    println!("building the grid took {:.3?}", start.elapsed());
+
+    let mut all_match = true;
+
+    let radius = QUERY_RADIUS * TILE_SIZE;
+    let centres: Vec<_> = (0..num_queries).map(|_| random.position(half_width)).collect();
+    let (grid_time, grid_results) = time(&centres, |centre| {
+        sorted(grid.within_radius(centre, radius))
+    });
+    let (brute_time, brute_results) = time(&centres, |centre| {
+        sorted(
+            placed
+                .iter()
+                .filter(|(_, position)| (position - centre).norm_squared() <= radius * radius)
+                .map(|(entity, _)| *entity)
+                .collect(),
+        )
+    });
+    all_match &= report("radius", grid_time, brute_time, grid_results == brute_results);
+
+    let size = Vector2::new(QUERY_RECT_SIZE, QUERY_RECT_SIZE) * TILE_SIZE;
+    let rects: Vec<_> = centres
+        .iter()
+        .map(|centre| (centre - size / 2.0, centre + size / 2.0))
+        .collect();
+    let (grid_time, grid_results) = time(&rects, |(min, max)| {
+        sorted(grid.within_rect(min, max))
+    });
+    let (brute_time, brute_results) = time(&rects, |(min, max)| {
+        sorted(
+            placed
+                .iter()
+                .filter(|(_, p)| p.x >= min.x && p.x <= max.x && p.y >= min.y && p.y <= max.y)
+                .map(|(entity, _)| *entity)
+                .collect(),
+        )
+    });
+    all_match &= report("rect", grid_time, brute_time, grid_results == brute_results);
+
+    let length = RAY_LENGTH * TILE_SIZE;
+    let rays: Vec<_> = centres
+        .iter()
+        .map(|origin| (*origin, random.position(1.0)))
+        .collect();
+    let (grid_time, grid_results) = time(&rays, |(origin, direction)| {
+        grid.raycast(origin, direction, length, RAY_RADIUS)
+    });
+    let (brute_time, brute_results) = time(&rays, |(origin, direction)| {
+        let direction = match direction.try_normalize(f32::EPSILON) {
+            Some(direction) => direction,
+            None => return Vec::new(),
+        };
+        let mut hits: Vec<RayHit> = placed
+            .iter()
+            .filter_map(|(entity, position)| {
+                let offset = position - origin;
+                let distance = offset.dot(&direction);
+                let within = distance >= 0.0
+                    && distance <= length
+                    && (offset - direction * distance).norm_squared() <= RAY_RADIUS * RAY_RADIUS;
+                if within { Some(RayHit { entity: *entity, distance }) } else { None }
+            })
+            .collect();
+        hits.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal));
+        hits
+    });
+    let same_hits = grid_results
+        .into_iter()
+        .zip(brute_results)
+        .all(|(grid_hits, brute_hits)| {
+            sorted(grid_hits.iter().map(|hit| hit.entity).collect())
+                == sorted(brute_hits.iter().map(|hit| hit.entity).collect())
+        });
+    all_match &= report("raycast", grid_time, brute_time, same_hits);
+
+    if !all_match {
+        std::process::exit(1);
+    }
+}
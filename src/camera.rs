@@ -0,0 +1,234 @@
+use std::f32::consts::PI;
+
+use amethyst::{
+    ecs::DenseVecStorage,
+    ecs::prelude::{Component, Entity},
+    renderer::camera::Projection,
+};
+use nalgebra::{UnitQuaternion, Vector3};
+
+use crate::level::MapBounds;
+use crate::utils::value_noise;
+
+/// Vertical field of view of the game camera, in radians
+pub const CAMERA_FOV: f32 = PI / 3.0;
+pub const CAMERA_Z_NEAR: f32 = 0.01;
+pub const CAMERA_Z_FAR: f32 = 1024.0;
+/// Used until the window's dimensions are known
+pub const DEFAULT_ASPECT_RATIO: f32 = 4.0 / 3.0;
+
+// The game camera's projection, for a window with the given aspect ratio.
+//
+// The CameraAspectSystem keeps the aspect ratio in step with the window.
+pub fn perspective_projection(aspect_ratio: f32) -> Projection {
+    Projection::perspective(aspect_ratio, CAMERA_FOV, CAMERA_Z_NEAR, CAMERA_Z_FAR)
+}
+
+// Makes the camera smoothly track a target entity (e.g. the Player).
+//
+// The camera is moved to `offset` from the focus point,
+//  which leads the target in its direction of travel.
+pub struct CameraFollow {
+    pub target: Entity,
+    /// Camera position relative to the focus point
+    pub offset: Vector3<f32>,
+    /// Camera orientation
+    pub rotation: UnitQuaternion<f32>,
+    /// How quickly the camera catches up with the focus point; higher is snappier
+    pub damping: f32,
+    /// How far the focus point leads the target, in seconds of its velocity
+    pub look_ahead: f32,
+    /// The focus point is kept within these bounds (e.g. the map)
+    pub bounds: Option<MapBounds>,
+    /// When set, the camera focuses here instead of following the target
+    pub focus_override: Option<Vector3<f32>>,
+    /// The camera's (smoothed) position, before any effects
+    pub position: Option<Vector3<f32>>,
+    /// The target's (smoothed) velocity
+    pub velocity: Vector3<f32>,
+    pub last_target_position: Option<Vector3<f32>>,
+}
+
+impl Component for CameraFollow {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl CameraFollow {
+    pub fn new(
+        target: Entity,
+        offset: Vector3<f32>,
+        rotation: UnitQuaternion<f32>
+    ) -> Self {
+        CameraFollow {
+            target,
+            offset,
+            rotation,
+            damping: 4.0,
+            look_ahead: 0.75,
+            bounds: None,
+            focus_override: None,
+            position: None,
+            velocity: Vector3::zeros(),
+            last_target_position: None,
+        }
+    }
+
+    pub fn with_bounds(mut self, bounds: Option<MapBounds>) -> Self {
+        self.bounds = bounds;
+        self
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Follow the target from a fixed angle
+    Follow,
+    /// Follow the target, and rotate around it with the orbit keys
+    ///  or by dragging the mouse
+    Orbit,
+    /// Move the focus point freely, by scrolling at the edge of the screen
+    ///  or by dragging the mouse
+    Pan,
+}
+
+impl CameraMode {
+    pub fn next(&self) -> CameraMode {
+        match self {
+            CameraMode::Follow => CameraMode::Orbit,
+            CameraMode::Orbit => CameraMode::Pan,
+            CameraMode::Pan => CameraMode::Follow,
+        }
+    }
+}
+
+// Input-driven zoom, orbit and pan for a camera with `CameraFollow`.
+//
+// The CameraControlSystem updates the CameraFollow's offset and rotation
+//  (and focus, when panning) from this.
+pub struct CameraController {
+    pub mode: CameraMode,
+    /// Distance from the focus point
+    pub distance: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Rotation around the focus point, in radians
+    pub yaw: f32,
+    /// Angle looking down at the focus point, in radians
+    pub pitch: f32,
+    /// Fraction of the distance each mouse wheel step zooms by
+    pub zoom_speed: f32,
+    /// Radians per second, using the orbit keys
+    pub orbit_speed: f32,
+    /// Units per second, when edge-scrolling
+    pub pan_speed: f32,
+    /// Width of the screen edge (in pixels) which scrolls when panning
+    pub edge_scroll_margin: f32,
+    /// Focus point when panning
+    pub pan_focus: Vector3<f32>,
+}
+
+impl Component for CameraController {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl Default for CameraController {
+    fn default() -> Self {
+        CameraController {
+            mode: CameraMode::Follow,
+            distance: 15.0 * 2.0_f32.sqrt(),
+            min_distance: 8.0,
+            max_distance: 60.0,
+            yaw: 0.0,
+            pitch: PI / 4.0,
+            zoom_speed: 0.1,
+            orbit_speed: PI / 2.0,
+            pan_speed: 12.0,
+            edge_scroll_margin: 16.0,
+            pan_focus: Vector3::zeros(),
+        }
+    }
+}
+
+impl CameraController {
+    pub fn rotation(&self) -> UnitQuaternion<f32> {
+        UnitQuaternion::from_euler_angles(-self.pitch, self.yaw, 0.0)
+    }
+
+    // Camera position relative to the focus point.
+    pub fn offset(&self) -> Vector3<f32> {
+        self.rotation() * Vector3::new(0.0, 0.0, self.distance)
+    }
+
+    // Directions on the ground plane for "right" and "up" on the screen.
+    pub fn ground_axes(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let (sin, cos) = self.yaw.sin_cos();
+        let right = Vector3::new(cos, 0.0, -sin);
+        let forward = Vector3::new(-sin, 0.0, -cos);
+        (right, forward)
+    }
+}
+
+// Trauma-based shake, applied on top of the camera's pose.
+//
+// Gameplay events add trauma, which decays over time;
+//  the shake's strength is the square of the trauma.
+pub struct CameraShake {
+    /// From 0.0 (still) to 1.0 (maximum shake)
+    pub trauma: f32,
+    /// Trauma lost per second
+    pub decay: f32,
+    /// Largest offset of the camera's position, at full trauma
+    pub max_offset: f32,
+    /// Largest rotation of the camera (in radians), at full trauma
+    pub max_angle: f32,
+    /// How quickly the shake changes direction
+    pub frequency: f32,
+    pub seed: u32,
+    /// Time the noise is sampled at
+    pub time: f32,
+}
+
+impl Component for CameraShake {
+    type Storage = DenseVecStorage<Self>;
+}
+
+impl Default for CameraShake {
+    fn default() -> Self {
+        CameraShake {
+            trauma: 0.0,
+            decay: 1.0,
+            max_offset: 0.5,
+            max_angle: 0.05,
+            frequency: 15.0,
+            seed: 0,
+            time: 0.0,
+        }
+    }
+}
+
+impl CameraShake {
+    pub fn add_trauma(&mut self, amount: f32) -> () {
+        self.trauma = (self.trauma + amount).max(0.0).min(1.0);
+    }
+
+    // Advance the shake by `dt` seconds.
+    pub fn update(&mut self, dt: f32) -> () {
+        self.time += dt;
+        self.trauma = (self.trauma - self.decay * dt).max(0.0);
+    }
+
+    // The current (position offset, rotation) to apply to the camera.
+    pub fn displacement(&self) -> (Vector3<f32>, UnitQuaternion<f32>) {
+        let shake = self.trauma * self.trauma;
+        let t = self.time * self.frequency;
+        let sample = |channel: u32| shake * value_noise(self.seed.wrapping_add(channel), t);
+
+        let offset = Vector3::new(sample(0), sample(1), sample(2)) * self.max_offset;
+        let rotation = UnitQuaternion::from_euler_angles(
+            sample(3) * self.max_angle,
+            sample(4) * self.max_angle,
+            sample(5) * self.max_angle,
+        );
+        (offset, rotation)
+    }
+}
@@ -0,0 +1,472 @@
+// Collision shapes on the ground plane, and the geometry for detecting their overlaps.
+//
+// Collisions are found in two phases:
+//  the broad phase (the SpatialGrid) finds pairs of colliders in the same map cells,
+//  and the narrow phase (`contact`) checks whether each pair actually overlaps.
+//
+// Positions on the ground plane are (x, z) in a Vector2.
+
+use std::convert::TryFrom;
+use std::path::Path;
+
+use amethyst::{
+    core::transform::Transform,
+    ecs::DenseVecStorage,
+    ecs::prelude::Component,
+    error::Error,
+};
+use nalgebra::{Matrix4, Point3, Vector2};
+use serde::Deserialize;
+
+use crate::assets::AssetManifest;
+use crate::utils::{heading, yaw_of_direction};
+
+// The shape of a Collider, centred on the entity (plus the collider's offset).
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum ColliderShape {
+    Circle { radius: f32 },
+    /// A box aligned with the world's axes, however the entity is rotated
+    Aabb { half_extents: (f32, f32) },
+    /// A box which rotates with the entity
+    Obb { half_extents: (f32, f32) },
+}
+
+// ColliderShape as written in unit definitions.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum SerializedColliderShape {
+    Circle { radius: f32 },
+    Aabb { half_extents: (f32, f32) },
+    Obb { half_extents: (f32, f32) },
+    /// An Obb fitting the unit's glTF mesh, from above
+    MeshBounds,
+}
+
+impl TryFrom<SerializedColliderShape> for ColliderShape {
+    type Error = ();
+
+    // Fails for MeshBounds, which must be resolved from the mesh.
+    fn try_from(shape: SerializedColliderShape) -> Result<Self, Self::Error> {
+        match shape {
+            SerializedColliderShape::Circle { radius } => Ok(ColliderShape::Circle { radius }),
+            SerializedColliderShape::Aabb { half_extents } => {
+                Ok(ColliderShape::Aabb { half_extents })
+            }
+            SerializedColliderShape::Obb { half_extents } => {
+                Ok(ColliderShape::Obb { half_extents })
+            }
+            SerializedColliderShape::MeshBounds => Err(()),
+        }
+    }
+}
+
+// A Collider as written in unit definitions.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct ColliderDefinition {
+    pub shape: SerializedColliderShape,
+    /// From the entity's origin, in its local space, as (x, z)
+    #[serde(default)]
+    pub offset: (f32, f32),
+    #[serde(default = "default_solid")]
+    pub solid: bool,
+}
+
+fn default_solid() -> bool {
+    true
+}
+
+impl ColliderDefinition {
+    // Replace a MeshBounds shape with an Obb fitting the mesh.
+    pub fn resolve_mesh_bounds(
+        &mut self,
+        manifest: &AssetManifest,
+        mesh: &str,
+    ) -> Result<(), Error> {
+        if self.shape != SerializedColliderShape::MeshBounds {
+            return Ok(());
+        }
+        let (min, max) = mesh_ground_bounds(manifest.mesh_path(mesh)?)?;
+        let half_extents = (max - min) / 2.0;
+        let centre = (min + max) / 2.0;
+        self.shape = SerializedColliderShape::Obb {
+            half_extents: (half_extents.x, half_extents.y),
+        };
+        self.offset = (self.offset.0 + centre.x, self.offset.1 + centre.y);
+        Ok(())
+    }
+}
+
+// The area an entity occupies on the ground plane, for collisions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Collider {
+    pub shape: ColliderShape,
+    /// From the entity's origin, in its local space, as (x, z)
+    pub offset: Vector2<f32>,
+    /// Solid colliders are pushed apart when they overlap;
+    ///  other colliders (e.g. shells) only report collisions
+    pub solid: bool,
+}
+
+impl Collider {
+    pub fn new(shape: ColliderShape) -> Self {
+        Collider {
+            shape,
+            offset: Vector2::zeros(),
+            solid: true,
+        }
+    }
+
+    // Where the collider is, for an entity with the given transform.
+    pub fn placed(&self, transform: &Transform) -> PlacedShape {
+        let yaw = yaw_of_direction(&heading(transform));
+        let translation = transform.translation();
+        PlacedShape {
+            shape: self.shape,
+            centre: Vector2::new(translation.x, translation.z) + rotate(&self.offset, yaw),
+            yaw: match self.shape {
+                ColliderShape::Aabb { .. } => 0.0,
+                _ => yaw,
+            },
+        }
+    }
+}
+
+impl TryFrom<&ColliderDefinition> for Collider {
+    type Error = Error;
+
+    fn try_from(definition: &ColliderDefinition) -> Result<Self, Self::Error> {
+        let shape = ColliderShape::try_from(definition.shape).map_err(|_| {
+            Error::from_string("collider's mesh bounds haven't been resolved")
+        })?;
+        let (x, z) = definition.offset;
+        Ok(Collider {
+            shape,
+            offset: Vector2::new(x, z),
+            solid: definition.solid,
+        })
+    }
+}
+
+impl Component for Collider {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Rotate a vector on the ground plane by a yaw, the same way as
+//  a Transform rotated about the Y axis (+Z is 0, +X is PI / 2).
+pub fn rotate(vector: &Vector2<f32>, yaw: f32) -> Vector2<f32> {
+    let (sin, cos) = yaw.sin_cos();
+    Vector2::new(
+        vector.x * cos + vector.y * sin,
+        -vector.x * sin + vector.y * cos,
+    )
+}
+
+// A collider's shape, where it is in the world.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlacedShape {
+    pub shape: ColliderShape,
+    pub centre: Vector2<f32>,
+    /// Always 0.0 for an Aabb
+    pub yaw: f32,
+}
+
+impl PlacedShape {
+    // The box's local X and Z axes, in world space.
+    fn axes(&self) -> [Vector2<f32>; 2] {
+        [rotate(&Vector2::x(), self.yaw), rotate(&Vector2::y(), self.yaw)]
+    }
+
+    // Corners of the bounding box aligned with the world's axes, as (min, max).
+    pub fn bounds(&self) -> (Vector2<f32>, Vector2<f32>) {
+        let extent = match self.shape {
+            ColliderShape::Circle { radius } => Vector2::new(radius, radius),
+            ColliderShape::Aabb { half_extents: (x, z) }
+            | ColliderShape::Obb { half_extents: (x, z) } => {
+                let [axis_x, axis_z] = self.axes();
+                axis_x.abs() * x + axis_z.abs() * z
+            }
+        };
+        (self.centre - extent, self.centre + extent)
+    }
+
+    // Half the shape's width along an axis.
+    fn projected_radius(&self, axis: &Vector2<f32>) -> f32 {
+        match self.shape {
+            ColliderShape::Circle { radius } => radius,
+            ColliderShape::Aabb { half_extents: (x, z) }
+            | ColliderShape::Obb { half_extents: (x, z) } => {
+                let [axis_x, axis_z] = self.axes();
+                axis_x.dot(axis).abs() * x + axis_z.dot(axis).abs() * z
+            }
+        }
+    }
+}
+
+// How two shapes overlap.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    /// Unit vector from the first shape towards the second;
+    ///  moving the second shape `depth` along it separates them
+    pub normal: Vector2<f32>,
+    pub depth: f32,
+}
+
+// Whether (and how) two shapes overlap.
+pub fn contact(a: &PlacedShape, b: &PlacedShape) -> Option<Contact> {
+    match (a.shape, b.shape) {
+        (ColliderShape::Circle { radius: radius_a }, ColliderShape::Circle { radius: radius_b }) => {
+            let between = b.centre - a.centre;
+            let distance = between.norm();
+            let depth = radius_a + radius_b - distance;
+            if depth <= 0.0 {
+                return None;
+            }
+            let normal = if distance > 0.0 { between / distance } else { Vector2::x() };
+            Some(Contact { normal, depth })
+        }
+        (ColliderShape::Circle { radius }, _) => {
+            circle_box_contact(&a.centre, radius, b).map(|contact| Contact {
+                normal: -contact.normal,
+                depth: contact.depth,
+            })
+        }
+        (_, ColliderShape::Circle { radius }) => circle_box_contact(&b.centre, radius, a),
+        _ => box_box_contact(a, b),
+    }
+}
+
+// Contact from a box to a circle, by finding the box's closest point to the circle.
+fn circle_box_contact(
+    circle_centre: &Vector2<f32>,
+    radius: f32,
+    placed_box: &PlacedShape,
+) -> Option<Contact> {
+    let (half_x, half_z) = match placed_box.shape {
+        ColliderShape::Aabb { half_extents } | ColliderShape::Obb { half_extents } => half_extents,
+        ColliderShape::Circle { .. } => return None,
+    };
+    let [axis_x, axis_z] = placed_box.axes();
+    let offset = circle_centre - placed_box.centre;
+    let local = Vector2::new(offset.dot(&axis_x), offset.dot(&axis_z));
+
+    let inside = local.x.abs() <= half_x && local.y.abs() <= half_z;
+    if inside {
+        // Push out through the nearest side.
+        let depth_x = half_x - local.x.abs();
+        let depth_z = half_z - local.y.abs();
+        let (normal, depth) = if depth_x < depth_z {
+            (axis_x * local.x.signum(), depth_x)
+        } else {
+            (axis_z * local.y.signum(), depth_z)
+        };
+        return Some(Contact { normal, depth: depth + radius });
+    }
+
+    let closest = Vector2::new(
+        local.x.max(-half_x).min(half_x),
+        local.y.max(-half_z).min(half_z),
+    );
+    let between = local - closest;
+    let distance = between.norm();
+    if distance >= radius {
+        return None;
+    }
+    let normal = (axis_x * between.x + axis_z * between.y) / distance;
+    Some(Contact { normal, depth: radius - distance })
+}
+
+// Contact between two boxes, with the separating axis test:
+//  boxes overlap unless they're separated along one of their axes.
+fn box_box_contact(a: &PlacedShape, b: &PlacedShape) -> Option<Contact> {
+    let between = b.centre - a.centre;
+    let mut best: Option<Contact> = None;
+    for axis in a.axes().iter().chain(b.axes().iter()) {
+        let distance = between.dot(axis);
+        let depth = a.projected_radius(axis) + b.projected_radius(axis) - distance.abs();
+        if depth <= 0.0 {
+            return None;
+        }
+        let shallower = match best {
+            Some(best) => depth < best.depth,
+            None => true,
+        };
+        if shallower {
+            let normal = if distance < 0.0 { -axis } else { *axis };
+            best = Some(Contact { normal, depth });
+        }
+    }
+    best
+}
+
+// The bounds of a glTF scene's meshes on the ground plane, as (min, max).
+pub fn mesh_ground_bounds<P: AsRef<Path>>(path: P) -> Result<(Vector2<f32>, Vector2<f32>), Error> {
+    let path = path.as_ref();
+    let gltf = gltf::Gltf::open(path).map_err(|err| {
+        Error::from_string(format!("Failed to read glTF file {}: {}", path.display(), err))
+    })?;
+    let scene = gltf
+        .default_scene()
+        .or_else(|| gltf.scenes().next())
+        .ok_or_else(|| Error::from_string(format!("No scene in {}", path.display())))?;
+
+    let mut bounds: Option<(Vector2<f32>, Vector2<f32>)> = None;
+    let mut nodes: Vec<(gltf::Node, Matrix4<f32>)> = scene
+        .nodes()
+        .map(|node| (node, Matrix4::identity()))
+        .collect();
+    while let Some((node, parent_matrix)) = nodes.pop() {
+        let matrix = parent_matrix * Matrix4::from(node.transform().matrix());
+        if let Some(mesh) = node.mesh() {
+            for primitive in mesh.primitives() {
+                let bounding_box = primitive.bounding_box();
+                let (min, max) = (bounding_box.min, bounding_box.max);
+                for &x in &[min[0], max[0]] {
+                    for &y in &[min[1], max[1]] {
+                        for &z in &[min[2], max[2]] {
+                            let corner = matrix.transform_point(&Point3::new(x, y, z));
+                            let corner = Vector2::new(corner.x, corner.z);
+                            bounds = Some(match bounds {
+                                Some((min, max)) => (
+                                    Vector2::new(min.x.min(corner.x), min.y.min(corner.y)),
+                                    Vector2::new(max.x.max(corner.x), max.y.max(corner.y)),
+                                ),
+                                None => (corner, corner),
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        nodes.extend(node.children().map(|child| (child, matrix)));
+    }
+    bounds.ok_or_else(|| Error::from_string(format!("No meshes in {}", path.display())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    use super::*;
+
+    fn circle(x: f32, z: f32, radius: f32) -> PlacedShape {
+        PlacedShape {
+            shape: ColliderShape::Circle { radius },
+            centre: Vector2::new(x, z),
+            yaw: 0.0,
+        }
+    }
+
+    fn aabb(x: f32, z: f32, half_x: f32, half_z: f32) -> PlacedShape {
+        PlacedShape {
+            shape: ColliderShape::Aabb { half_extents: (half_x, half_z) },
+            centre: Vector2::new(x, z),
+            yaw: 0.0,
+        }
+    }
+
+    fn obb(x: f32, z: f32, half_x: f32, half_z: f32, yaw: f32) -> PlacedShape {
+        PlacedShape {
+            shape: ColliderShape::Obb { half_extents: (half_x, half_z) },
+            centre: Vector2::new(x, z),
+            yaw,
+        }
+    }
+
+    fn assert_contact(contact: Option<Contact>, normal: (f32, f32), depth: f32) {
+        let contact = contact.expect("shapes should overlap");
+        assert!(
+            (contact.normal - Vector2::new(normal.0, normal.1)).norm() < 1e-4,
+            "normal {:?}, expected {:?}", contact.normal, normal,
+        );
+        assert!(
+            (contact.depth - depth).abs() < 1e-4,
+            "depth {}, expected {}", contact.depth, depth,
+        );
+    }
+
+    #[test]
+    fn circles_overlap_along_the_line_between_them() {
+        assert_contact(contact(&circle(0.0, 0.0, 1.0), &circle(1.5, 0.0, 1.0)), (1.0, 0.0), 0.5);
+        assert_contact(contact(&circle(0.0, 1.5, 1.0), &circle(0.0, 0.0, 1.0)), (0.0, -1.0), 0.5);
+    }
+
+    #[test]
+    fn separate_circles_dont_touch() {
+        assert_eq!(contact(&circle(0.0, 0.0, 1.0), &circle(2.5, 0.0, 1.0)), None);
+    }
+
+    #[test]
+    fn circle_and_aabb_overlap() {
+        let box_shape = aabb(1.5, 0.0, 1.0, 1.0);
+        assert_contact(contact(&circle(0.0, 0.0, 1.0), &box_shape), (1.0, 0.0), 0.5);
+        // The normal always points from the first shape to the second.
+        assert_contact(contact(&box_shape, &circle(0.0, 0.0, 1.0)), (-1.0, 0.0), 0.5);
+    }
+
+    #[test]
+    fn circle_and_aabb_separate() {
+        assert_eq!(contact(&circle(0.0, 0.0, 1.0), &aabb(2.5, 0.0, 1.0, 1.0)), None);
+        // Near the box's corner, but outside it.
+        assert_eq!(contact(&circle(0.0, 0.0, 1.0), &aabb(1.8, 1.8, 1.0, 1.0)), None);
+    }
+
+    #[test]
+    fn circle_and_obb_use_the_boxs_rotation() {
+        // Turned a quarter, the box's long (local X) side runs along Z.
+        let box_shape = obb(0.0, 0.0, 2.0, 0.5, FRAC_PI_2);
+        assert_contact(contact(&box_shape, &circle(0.0, 2.2, 0.5)), (0.0, 1.0), 0.3);
+        assert_eq!(contact(&box_shape, &circle(1.0, 0.0, 0.4)), None);
+        // Unrotated, the same circle would overlap.
+        assert!(contact(&obb(0.0, 0.0, 2.0, 0.5, 0.0), &circle(1.0, 0.0, 0.4)).is_some());
+    }
+
+    #[test]
+    fn circle_inside_a_box_is_pushed_out_through_the_nearest_side() {
+        let box_shape = aabb(0.0, 0.0, 2.0, 1.0);
+        assert_contact(contact(&box_shape, &circle(0.5, 0.2, 0.3)), (0.0, 1.0), 1.1);
+        assert_contact(contact(&box_shape, &circle(-1.9, 0.0, 0.3)), (-1.0, 0.0), 0.4);
+    }
+
+    #[test]
+    fn rotated_boxes_overlap_along_the_axis_of_least_depth() {
+        let a = aabb(0.0, 0.0, 1.0, 1.0);
+        let b = obb(2.2, 0.0, 1.0, 1.0, FRAC_PI_4);
+        // Along X, `b` reaches sqrt(2) from its centre.
+        let depth = 1.0 + 2.0_f32.sqrt() - 2.2;
+        assert_contact(contact(&a, &b), (1.0, 0.0), depth);
+        assert_contact(contact(&b, &a), (-1.0, 0.0), depth);
+    }
+
+    #[test]
+    fn rotated_boxes_separated_along_one_of_their_axes() {
+        let a = aabb(0.0, 0.0, 1.0, 1.0);
+        assert_eq!(contact(&a, &obb(2.5, 0.0, 1.0, 1.0, FRAC_PI_4)), None);
+        // Their bounds overlap, but the thin box's own axis separates them.
+        let thin = obb(1.6, 1.6, 1.0, 0.1, FRAC_PI_4);
+        let (min, _) = thin.bounds();
+        assert!(min.x < 1.0 && min.y < 1.0);
+        assert_eq!(contact(&a, &thin), None);
+    }
+
+    #[test]
+    fn aabb_ignores_the_entitys_rotation() {
+        let mut transform = Transform::default();
+        transform.set_translation_xyz(1.0, 0.0, 2.0);
+        transform.set_rotation_y_axis(FRAC_PI_4);
+        let mut collider = Collider::new(ColliderShape::Aabb { half_extents: (1.0, 0.5) });
+        assert_eq!(collider.placed(&transform).yaw, 0.0);
+
+        collider.shape = ColliderShape::Obb { half_extents: (1.0, 0.5) };
+        let placed = collider.placed(&transform);
+        assert!((placed.yaw - FRAC_PI_4).abs() < 1e-4);
+        assert!((placed.centre - Vector2::new(1.0, 2.0)).norm() < 1e-4);
+    }
+
+    #[test]
+    fn mesh_bounds_fit_the_tank() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/mesh/tank.gltf");
+        let (min, max) = mesh_ground_bounds(path).expect("tank.gltf should load");
+        // The tracks are the widest part, and the base the longest.
+        assert!((min.x + 0.85).abs() < 0.01 && (max.x - 0.85).abs() < 0.01);
+        assert!(min.y < -0.99 && max.y > 0.99 && max.y < 1.1);
+    }
+}
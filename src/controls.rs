@@ -0,0 +1,174 @@
+// Tuning of how analog input (e.g. gamepad sticks) maps to movement.
+//
+// Keyboard axes are always -1, 0 or 1, and are unaffected by this.
+
+use std::fs::File;
+use std::path::Path;
+
+use amethyst::error::Error;
+use ron::de::from_reader as from_ron_reader;
+use serde::{Deserialize, Serialize,};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlsConfig {
+    /// Axis values with a smaller magnitude than this are treated as 0
+    pub dead_zone: f32,
+    /// Exponent applied to the axis magnitude (after the dead zone).
+    /// 1.0 is linear; larger values give finer control near the centre.
+    pub response_exponent: f32,
+}
+
+impl Default for ControlsConfig {
+    fn default() -> Self {
+        ControlsConfig {
+            dead_zone: 0.15,
+            response_exponent: 2.0,
+        }
+    }
+}
+
+impl ControlsConfig {
+    // Load the config from e.g. `resources/controls.ron`,
+    //  checking that its values are in range.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ControlsConfig, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| {
+            Error::from_string(format!(
+                "Failed to open controls config {}: {}", path.display(), err
+            ))
+        })?;
+        let config: ControlsConfig = from_ron_reader(file).map_err(|err| {
+            Error::from_string(format!(
+                "Failed to parse controls config {}: {}", path.display(), err
+            ))
+        })?;
+        config.validate().map_err(|problem| {
+            Error::from_string(format!("Bad controls config {}: {}", path.display(), problem))
+        })?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        // `apply` divides by (1.0 - dead_zone).
+        if !(0.0..1.0).contains(&self.dead_zone) {
+            return Err(format!("dead_zone must be in 0.0..1.0, not {}", self.dead_zone));
+        }
+        if !(self.response_exponent > 0.0 && self.response_exponent.is_finite()) {
+            return Err(format!(
+                "response_exponent must be positive, not {}", self.response_exponent
+            ));
+        }
+        Ok(())
+    }
+
+    // Apply the dead zone and response curve to an axis value.
+    //
+    // The result is in -1.0..=1.0, with the same sign as the input.
+    pub fn apply(&self, value: f32) -> f32 {
+        let magnitude = value.abs().min(1.0);
+        if magnitude < self.dead_zone {
+            return 0.0;
+        }
+
+        let scaled = (magnitude - self.dead_zone) / (1.0 - self.dead_zone);
+        scaled.powf(self.response_exponent).copysign(value)
+    }
+}
+
+// How the movement input is interpreted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ControlScheme {
+    /// Turn left/right, drive forward/back along the tank's heading
+    Tank,
+    /// The movement input is a direction relative to the camera;
+    ///  the tank turns towards it then drives.
+    /// The turret tracks the mouse cursor.
+    CameraRelative,
+}
+
+impl Default for ControlScheme {
+    fn default() -> Self {
+        ControlScheme::Tank
+    }
+}
+
+impl ControlScheme {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ControlScheme::Tank => "Tank",
+            ControlScheme::CameraRelative => "Camera-relative",
+        }
+    }
+
+    pub fn next(&self) -> ControlScheme {
+        match self {
+            ControlScheme::Tank => ControlScheme::CameraRelative,
+            ControlScheme::CameraRelative => ControlScheme::Tank,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(dead_zone: f32, response_exponent: f32) -> ControlsConfig {
+        ControlsConfig { dead_zone, response_exponent }
+    }
+
+    fn assert_close(actual: f32, expected: f32) -> () {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn values_within_the_dead_zone_are_zero() {
+        let controls = config(0.2, 1.0);
+        assert_eq!(controls.apply(0.0), 0.0);
+        assert_eq!(controls.apply(0.19), 0.0);
+        assert_eq!(controls.apply(-0.19), 0.0);
+        assert_close(controls.apply(0.2), 0.0);
+    }
+
+    #[test]
+    fn the_range_outside_the_dead_zone_is_rescaled() {
+        let controls = config(0.2, 1.0);
+        assert_close(controls.apply(0.6), 0.5);
+        assert_close(controls.apply(1.0), 1.0);
+        // Beyond full deflection is clamped.
+        assert_close(controls.apply(1.5), 1.0);
+    }
+
+    #[test]
+    fn the_response_curve_is_applied_to_the_magnitude() {
+        let controls = config(0.2, 2.0);
+        assert_close(controls.apply(0.6), 0.25);
+        assert_close(controls.apply(-0.6), -0.25);
+        assert_close(controls.apply(-1.0), -1.0);
+        assert_close(config(0.0, 3.0).apply(-0.5), -0.125);
+    }
+
+    #[test]
+    fn keyboard_axes_are_unaffected() {
+        let controls = ControlsConfig::default();
+        assert_eq!(controls.apply(1.0), 1.0);
+        assert_eq!(controls.apply(-1.0), -1.0);
+        assert_eq!(controls.apply(0.0), 0.0);
+    }
+
+    #[test]
+    fn dead_zone_must_be_less_than_one() {
+        assert!(ControlsConfig::default().validate().is_ok());
+        assert!(config(0.0, 1.0).validate().is_ok());
+        assert!(config(1.0, 1.0).validate().is_err());
+        assert!(config(-0.1, 1.0).validate().is_err());
+        assert!(config(f32::NAN, 1.0).validate().is_err());
+        assert!(config(0.1, 0.0).validate().is_err());
+    }
+
+    #[test]
+    fn the_games_config_loads() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/controls.ron");
+        assert!(ControlsConfig::load(path).is_ok());
+    }
+}
@@ -0,0 +1,19 @@
+use amethyst::ecs::prelude::Entity;
+use nalgebra::{Vector2, Vector3};
+
+// Things happening in the game which other systems
+//  (e.g. camera shake) react to.
+//
+// Sent through an `EventChannel<GameplayEvent>` resource.
+#[derive(Clone, Debug)]
+pub enum GameplayEvent {
+    /// The entity fired its weapon, from the given position, in the given direction
+    Fired { entity: Entity, position: Vector3<f32>, direction: Vector3<f32> },
+    /// The entity took damage
+    Damaged { entity: Entity, amount: f32 },
+    /// An explosion, with a strength from 0.0 to 1.0
+    Explosion { position: Vector3<f32>, strength: f32 },
+    /// The entity's collider overlaps the other's;
+    ///  `normal` (on the ground plane, as (x, z)) points from the entity towards the other
+    Collided { entity: Entity, other: Entity, normal: Vector2<f32>, depth: f32 },
+}
@@ -0,0 +1,344 @@
+// Prints the contents of a glTF file:
+//  its scenes' node trees (with transforms, meshes and skins),
+//  and its materials (with their textures), skins and animations.
+//
+// Useful for finding the node and material names
+//  which e.g. material rules and team palettes refer to.
+//
+// With `--json`, prints the same as JSON, for tooling.
+
+use std::path::{Path, PathBuf};
+
+use gltf::{image, Document, Gltf, Node};
+use serde::Serialize;
+
+const USAGE: &str = "\
+Usage: inspect-gltf [options] <path>
+
+Options:
+    --json      Print JSON instead of text
+    --help      Print this message";
+
+#[derive(Serialize)]
+struct GltfInfo {
+    path: PathBuf,
+    scenes: Vec<SceneInfo>,
+    materials: Vec<MaterialInfo>,
+    skins: Vec<SkinInfo>,
+    animations: Vec<AnimationInfo>,
+}
+
+#[derive(Serialize)]
+struct SceneInfo {
+    index: usize,
+    name: Option<String>,
+    nodes: Vec<NodeInfo>,
+}
+
+#[derive(Serialize)]
+struct NodeInfo {
+    index: usize,
+    name: Option<String>,
+    translation: [f32; 3],
+    /// Quaternion, as (x, y, z, w)
+    rotation: [f32; 4],
+    scale: [f32; 3],
+    mesh: Option<MeshInfo>,
+    /// Index of the node's skin
+    skin: Option<usize>,
+    children: Vec<NodeInfo>,
+}
+
+#[derive(Serialize)]
+struct MeshInfo {
+    index: usize,
+    name: Option<String>,
+    primitives: Vec<PrimitiveInfo>,
+}
+
+#[derive(Serialize)]
+struct PrimitiveInfo {
+    mode: String,
+    /// Attribute semantics, with their number of elements
+    attributes: Vec<(String, usize)>,
+    /// `None` for the default material
+    material: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct MaterialInfo {
+    index: usize,
+    name: Option<String>,
+    base_colour: [f32; 4],
+    textures: Vec<TextureInfo>,
+}
+
+#[derive(Serialize)]
+struct TextureInfo {
+    /// How the material uses the texture, e.g. "base_colour"
+    usage: String,
+    index: usize,
+    name: Option<String>,
+    /// The image's URI, or which buffer view it's stored in
+    image: String,
+}
+
+#[derive(Serialize)]
+struct SkinInfo {
+    index: usize,
+    name: Option<String>,
+    joints: Vec<usize>,
+}
+
+#[derive(Serialize)]
+struct AnimationInfo {
+    index: usize,
+    name: Option<String>,
+    channels: Vec<ChannelInfo>,
+}
+
+#[derive(Serialize)]
+struct ChannelInfo {
+    node: usize,
+    property: String,
+    keyframes: usize,
+}
+
+fn name(name: Option<&str>) -> Option<String> {
+    name.map(String::from)
+}
+
+fn node_info(node: &Node) -> NodeInfo {
+    let (translation, rotation, scale) = node.transform().decomposed();
+    NodeInfo {
+        index: node.index(),
+        name: name(node.name()),
+        translation,
+        rotation,
+        scale,
+        mesh: node.mesh().map(|mesh| MeshInfo {
+            index: mesh.index(),
+            name: name(mesh.name()),
+            primitives: mesh
+                .primitives()
+                .map(|primitive| PrimitiveInfo {
+                    mode: format!("{:?}", primitive.mode()),
+                    attributes: primitive
+                        .attributes()
+                        .map(|(semantic, accessor)| (format!("{:?}", semantic), accessor.count()))
+                        .collect(),
+                    material: primitive.material().index(),
+                })
+                .collect(),
+        }),
+        skin: node.skin().map(|skin| skin.index()),
+        children: node.children().map(|child| node_info(&child)).collect(),
+    }
+}
+
+fn texture_info(usage: &str, texture: gltf::Texture) -> TextureInfo {
+    let image = match texture.source().source() {
+        image::Source::Uri { uri, .. } => uri.to_string(),
+        image::Source::View { view, mime_type } => {
+            format!("buffer view #{} ({})", view.index(), mime_type)
+        }
+    };
+    TextureInfo {
+        usage: usage.to_string(),
+        index: texture.index(),
+        name: name(texture.name()),
+        image,
+    }
+}
+
+fn material_info(material: gltf::Material) -> MaterialInfo {
+    let pbr = material.pbr_metallic_roughness();
+    let mut textures = Vec::new();
+    if let Some(info) = pbr.base_color_texture() {
+        textures.push(texture_info("base_colour", info.texture()));
+    }
+    if let Some(info) = pbr.metallic_roughness_texture() {
+        textures.push(texture_info("metallic_roughness", info.texture()));
+    }
+    if let Some(normal) = material.normal_texture() {
+        textures.push(texture_info("normal", normal.texture()));
+    }
+    if let Some(occlusion) = material.occlusion_texture() {
+        textures.push(texture_info("occlusion", occlusion.texture()));
+    }
+    if let Some(info) = material.emissive_texture() {
+        textures.push(texture_info("emissive", info.texture()));
+    }
+
+    MaterialInfo {
+        // Every material in the document has an index; only the default one doesn't.
+        index: material.index().unwrap_or_default(),
+        name: name(material.name()),
+        base_colour: pbr.base_color_factor(),
+        textures,
+    }
+}
+
+fn gltf_info(path: &Path, document: &Document) -> GltfInfo {
+    GltfInfo {
+        path: path.to_path_buf(),
+        scenes: document
+            .scenes()
+            .map(|scene| SceneInfo {
+                index: scene.index(),
+                name: name(scene.name()),
+                nodes: scene.nodes().map(|node| node_info(&node)).collect(),
+            })
+            .collect(),
+        materials: document.materials().map(material_info).collect(),
+        skins: document
+            .skins()
+            .map(|skin| SkinInfo {
+                index: skin.index(),
+                name: name(skin.name()),
+                joints: skin.joints().map(|joint| joint.index()).collect(),
+            })
+            .collect(),
+        animations: document
+            .animations()
+            .map(|animation| AnimationInfo {
+                index: animation.index(),
+                name: name(animation.name()),
+                channels: animation
+                    .channels()
+                    .map(|channel| ChannelInfo {
+                        node: channel.target().node().index(),
+                        property: format!("{:?}", channel.target().property()),
+                        keyframes: channel.sampler().input().count(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+// e.g. `#3 "turret"`, or `#3` if it has no name.
+fn label(index: usize, name: &Option<String>) -> String {
+    match name {
+        Some(name) => format!("#{} {:?}", index, name),
+        None => format!("#{}", index),
+    }
+}
+
+fn print_node(info: &GltfInfo, node: &NodeInfo, depth: usize) -> () {
+    let indent = "  ".repeat(depth);
+    println!("{}node {}", indent, label(node.index, &node.name));
+    println!(
+        "{}  translation {:?}, rotation {:?}, scale {:?}",
+        indent, node.translation, node.rotation, node.scale,
+    );
+    if let Some(mesh) = &node.mesh {
+        println!("{}  mesh {}", indent, label(mesh.index, &mesh.name));
+        for primitive in &mesh.primitives {
+            let material = match primitive.material {
+                Some(index) => label(index, &info.materials[index].name),
+                None => String::from("(default)"),
+            };
+            let attributes: Vec<String> = primitive
+                .attributes
+                .iter()
+                .map(|(semantic, count)| format!("{} ({})", semantic, count))
+                .collect();
+            println!(
+                "{}    primitive {}, material {}, attributes {}",
+                indent, primitive.mode, material, attributes.join(", "),
+            );
+        }
+    }
+    if let Some(skin) = node.skin {
+        println!("{}  skin {}", indent, label(skin, &info.skins[skin].name));
+    }
+    for child in &node.children {
+        print_node(info, child, depth + 1);
+    }
+}
+
+fn print_info(info: &GltfInfo) -> () {
+    println!("{}", info.path.display());
+
+    for scene in &info.scenes {
+        println!();
+        println!("scene {}", label(scene.index, &scene.name));
+        for node in &scene.nodes {
+            print_node(info, node, 1);
+        }
+    }
+
+    println!();
+    println!("materials:");
+    for material in &info.materials {
+        println!(
+            "  {}, base colour {:?}",
+            label(material.index, &material.name), material.base_colour,
+        );
+        for texture in &material.textures {
+            println!(
+                "    {} texture {}: {}",
+                texture.usage, label(texture.index, &texture.name), texture.image,
+            );
+        }
+    }
+
+    if !info.skins.is_empty() {
+        println!();
+        println!("skins:");
+        for skin in &info.skins {
+            println!("  {}, joints {:?}", label(skin.index, &skin.name), skin.joints);
+        }
+    }
+
+    if !info.animations.is_empty() {
+        println!();
+        println!("animations:");
+        for animation in &info.animations {
+            println!("  {}", label(animation.index, &animation.name));
+            for channel in &animation.channels {
+                println!(
+                    "    node #{} {}, {} keyframes",
+                    channel.node, channel.property, channel.keyframes,
+                );
+            }
+        }
+    }
+}
+
+fn main() -> () {
+    let mut json = false;
+    let mut path = None;
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--json" => json = true,
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            _ if path.is_none() && !arg.starts_with("--") => path = Some(PathBuf::from(arg)),
+            _ => {
+                eprintln!("unexpected argument '{}'\n\n{}", arg, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+    let path = path.unwrap_or_else(|| {
+        eprintln!("no glTF path given\n\n{}", USAGE);
+        std::process::exit(2);
+    });
+
+    let gltf = Gltf::open(&path).unwrap_or_else(|err| {
+        eprintln!("failed to open {}: {}", path.display(), err);
+        std::process::exit(1);
+    });
+    let info = gltf_info(&path, &gltf.document);
+
+    if json {
+        let output = serde_json::to_string_pretty(&info).expect("GltfInfo serializes");
+        println!("{}", output);
+    } else {
+        print_info(&info);
+    }
+}
@@ -0,0 +1,272 @@
+// Rebinding of the input bindings at runtime.
+//
+// Each emulated axis direction and each action is a `BindingSlot`,
+//  which can be assigned a single button.
+// Rebound bindings are saved to the user's `input.ron` override
+//  (see `preferences::user_input_config_path`).
+
+use std::fs;
+
+use amethyst::{
+    error::Error,
+    input::{Axis, Bindings, Button, InputEvent, StringBindings},
+};
+use err_derive::Error;
+use ron::ser::{to_string_pretty as to_ron_string_pretty, PrettyConfig};
+
+use crate::preferences::user_input_config_path;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BindingSlot {
+    AxisPositive(String),
+    AxisNegative(String),
+    Action(String),
+}
+
+#[derive(Debug, Error)]
+pub enum RebindError {
+    #[error(display = "already bound to {}", _0)]
+    Conflict(String),
+    #[error(display = "{} can't be rebound", _0)]
+    NotRebindable(String),
+    #[error(display = "{}", _0)]
+    Binding(String),
+}
+
+impl BindingSlot {
+    pub fn label(&self) -> String {
+        match self {
+            BindingSlot::AxisPositive(id) => format!("{} +", id),
+            BindingSlot::AxisNegative(id) => format!("{} -", id),
+            BindingSlot::Action(id) => id.clone(),
+        }
+    }
+}
+
+// The emulated (button) part of an axis,
+//  looking inside `Axis::Multiple` for one.
+fn emulated_buttons(axis: &Axis) -> Option<(&Button, &Button)> {
+    match axis {
+        Axis::Emulated { pos, neg } => Some((pos, neg)),
+        Axis::Multiple(axes) => axes.iter().filter_map(emulated_buttons).next(),
+        _ => None,
+    }
+}
+
+fn emulated_buttons_mut(axis: &mut Axis) -> Option<(&mut Button, &mut Button)> {
+    match axis {
+        Axis::Emulated { pos, neg } => Some((pos, neg)),
+        Axis::Multiple(axes) => axes.iter_mut().filter_map(emulated_buttons_mut).next(),
+        _ => None,
+    }
+}
+
+// The slot of the axis whose emulated buttons (in any of its parts) include the button.
+fn emulated_slot(id: &str, axis: &Axis, button: &Button) -> Option<BindingSlot> {
+    match axis {
+        Axis::Emulated { pos, .. } if pos == button => {
+            Some(BindingSlot::AxisPositive(id.to_string()))
+        }
+        Axis::Emulated { neg, .. } if neg == button => {
+            Some(BindingSlot::AxisNegative(id.to_string()))
+        }
+        Axis::Multiple(axes) => axes.iter().find_map(|axis| emulated_slot(id, axis, button)),
+        _ => None,
+    }
+}
+
+// All the slots which can be rebound, axes first, sorted by name.
+pub fn binding_slots(bindings: &Bindings<StringBindings>) -> Vec<BindingSlot> {
+    let mut axis_ids: Vec<String> = bindings.axes().cloned().collect();
+    axis_ids.sort();
+    let mut action_ids: Vec<String> = bindings.actions().cloned().collect();
+    action_ids.sort();
+
+    let mut slots = Vec::new();
+    for id in axis_ids {
+        let is_emulated = bindings
+            .axis(&id)
+            .and_then(emulated_buttons)
+            .is_some();
+        if is_emulated {
+            slots.push(BindingSlot::AxisPositive(id.clone()));
+            slots.push(BindingSlot::AxisNegative(id));
+        }
+    }
+    for id in action_ids {
+        slots.push(BindingSlot::Action(id));
+    }
+    slots
+}
+
+pub fn bound_button(
+    bindings: &Bindings<StringBindings>,
+    slot: &BindingSlot
+) -> Option<Button> {
+    match slot {
+        BindingSlot::AxisPositive(id) => bindings
+            .axis(id)
+            .and_then(emulated_buttons)
+            .map(|(pos, _)| pos.clone()),
+        BindingSlot::AxisNegative(id) => bindings
+            .axis(id)
+            .and_then(emulated_buttons)
+            .map(|(_, neg)| neg.clone()),
+        BindingSlot::Action(id) => bindings
+            .action_bindings(id)
+            .find(|combo| combo.len() == 1)
+            .map(|combo| combo[0].clone()),
+    }
+}
+
+// The slot the button is bound to, in any of the axes' parts
+//  or any of the actions' combos (not just those shown for rebinding).
+pub fn slot_for_button(
+    bindings: &Bindings<StringBindings>,
+    button: &Button
+) -> Option<BindingSlot> {
+    let axis_slot = bindings.axes().find_map(|id| {
+        bindings.axis(id).and_then(|axis| emulated_slot(id, axis, button))
+    });
+    axis_slot.or_else(|| {
+        bindings
+            .actions()
+            .find(|id| bindings.action_bindings(*id).any(|combo| combo.contains(button)))
+            .map(|id| BindingSlot::Action(id.clone()))
+    })
+}
+
+// Assign the button to the slot.
+//
+// Fails if the button is already bound to a different slot.
+pub fn rebind(
+    bindings: &mut Bindings<StringBindings>,
+    slot: &BindingSlot,
+    button: Button,
+) -> Result<(), RebindError> {
+    if let Some(bound_slot) = slot_for_button(bindings, &button) {
+        if &bound_slot == slot {
+            return Ok(());
+        }
+        return Err(RebindError::Conflict(bound_slot.label()));
+    }
+
+    match slot {
+        BindingSlot::AxisPositive(id) | BindingSlot::AxisNegative(id) => {
+            let mut axis = bindings
+                .remove_axis(id)
+                .ok_or_else(|| RebindError::NotRebindable(slot.label()))?;
+            let original_axis = axis.clone();
+            {
+                let (pos, neg) = emulated_buttons_mut(&mut axis)
+                    .ok_or_else(|| RebindError::NotRebindable(slot.label()))?;
+                if let BindingSlot::AxisPositive(_) = slot {
+                    *pos = button;
+                } else {
+                    *neg = button;
+                }
+            }
+            bindings.insert_axis(id.clone(), axis).map_err(|err| {
+                let _ = bindings.insert_axis(id.clone(), original_axis);
+                RebindError::Binding(format!("{}", err))
+            })?;
+        }
+        BindingSlot::Action(id) => {
+            let old_button = bound_button(bindings, slot);
+            if let Some(old_button) = &old_button {
+                bindings.remove_action_binding(id, &[old_button.clone()]);
+            }
+            bindings.insert_action_binding(id.clone(), vec![button]).map_err(|err| {
+                if let Some(old_button) = old_button {
+                    let _ = bindings.insert_action_binding(id.clone(), vec![old_button]);
+                }
+                RebindError::Binding(format!("{}", err))
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+// The button for an input event, if it's one which can be bound.
+pub fn button_from_input_event(event: &InputEvent<StringBindings>) -> Option<Button> {
+    match event {
+        // Key presses are reported both by key code and by scan code;
+        //  the key code is the more portable of the two.
+        InputEvent::ButtonPressed(Button::ScanCode(_)) => None,
+        InputEvent::ButtonPressed(button) => Some(button.clone()),
+        _ => None,
+    }
+}
+
+pub fn save_user_bindings(bindings: &Bindings<StringBindings>) -> Result<(), Error> {
+    let path = user_input_config_path().ok_or_else(|| {
+        Error::from_string("no config directory to save input bindings to")
+    })?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let serialized = to_ron_string_pretty(bindings, PrettyConfig::default())
+        .map_err(|_| Error::from_string("Failed to serialize input bindings"))?;
+    fs::write(path, serialized)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, path::Path};
+
+    use amethyst::{input::ControllerButton, winit::VirtualKeyCode};
+    use ron::de::from_reader as from_ron_reader;
+
+    use super::*;
+
+    fn game_bindings() -> Bindings<StringBindings> {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/input.ron");
+        from_ron_reader(File::open(path).expect("input.ron exists")).expect("input.ron parses")
+    }
+
+    fn action(id: &str) -> BindingSlot {
+        BindingSlot::Action(id.to_string())
+    }
+
+    #[test]
+    fn a_free_button_is_rebound() {
+        let mut bindings = game_bindings();
+        let button = Button::Key(VirtualKeyCode::G);
+        rebind(&mut bindings, &action("camera_snap"), button.clone()).unwrap();
+        assert_eq!(bound_button(&bindings, &action("camera_snap")), Some(button.clone()));
+        assert_eq!(slot_for_button(&bindings, &button), Some(action("camera_snap")));
+        assert_eq!(slot_for_button(&bindings, &Button::Key(VirtualKeyCode::F)), None);
+    }
+
+    #[test]
+    fn buttons_bound_to_axes_conflict() {
+        let mut bindings = game_bindings();
+        match rebind(&mut bindings, &action("camera_snap"), Button::Key(VirtualKeyCode::D)) {
+            Err(RebindError::Conflict(slot)) => assert_eq!(slot, "player_rotation -"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(
+            bound_button(&bindings, &action("camera_snap")),
+            Some(Button::Key(VirtualKeyCode::F)),
+        );
+    }
+
+    #[test]
+    fn secondary_bindings_conflict() {
+        let mut bindings = game_bindings();
+        let shoulder = Button::Controller(0, ControllerButton::RightShoulder);
+        match rebind(&mut bindings, &action("camera_snap"), shoulder) {
+            Err(RebindError::Conflict(slot)) => assert_eq!(slot, "fire"),
+            other => panic!("unexpected {:?}", other),
+        }
+        let slot = BindingSlot::AxisPositive("camera_orbit".to_string());
+        match rebind(&mut bindings, &slot, Button::Key(VirtualKeyCode::Space)) {
+            Err(RebindError::Conflict(slot)) => assert_eq!(slot, "fire"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -1,11 +1,15 @@
 use std::f32::consts::PI;
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::path::Path;
 
 use amethyst::{
     core::transform::Transform,
-    ecs::prelude::Write,
+    ecs::NullStorage,
+    ecs::prelude::{Component, Entities, Entity, Join, ReadStorage, Write},
+    error::Error,
     prelude::*,
     renderer::{
-        camera::Projection,
         light::{
             DirectionalLight,
             Light,
@@ -14,59 +18,367 @@ use amethyst::{
         palette::{Srgb, Srgba},
         Camera,
     },
+    window::ScreenDimensions,
 };
-use nalgebra::{Translation3, UnitQuaternion, Vector3,};
+use log::error;
+use nalgebra::{Translation3, Vector2, Vector3,};
+use ron::de::from_reader as from_ron_reader;
+use serde::{Deserialize, Serialize,};
 
-use crate::assets::Assets;
+use crate::assets::{AssetDependencies, AssetKey, AssetManifest, AssetRegistry, GridAsset};
+use crate::camera::{
+    perspective_projection,
+    CameraController,
+    CameraFollow,
+    CameraShake,
+    DEFAULT_ASPECT_RATIO,
+};
 use crate::player::init_player;
+use crate::replace_material::{load_material_rules, SerializedMaterialRule, HIT_FLASH_MATERIAL};
+use crate::spatial_grid::SpatialGrid;
+use crate::sprite_grid::{grid_half_extents, load_grid_dimensions, TILE_SIZE};
+use crate::team::{Team, TeamPalette};
+use crate::unit::{spawn_unit, UnitDefinition};
+use crate::utils::delete_hierarchy;
+
+// Marks entities which belong to the level,
+//  so that they can be torn down when the level is left.
+//
+// Descendants of a LevelEntity (e.g. the nodes spawned for a glTF scene)
+//  are deleted along with it.
+#[derive(Default)]
+pub struct LevelEntity;
+
+impl Component for LevelEntity {
+    type Storage = NullStorage<Self>;
+}
+
+static DEFAULT_MAP: &str = "basic_map";
+static DEFAULT_PLAYER_MATERIALS: &str = "material/player_tank.ron";
+
+static DEFAULT_PLAYER_TEAM: &str = "blue";
+static DEFAULT_PLAYER_UNIT: &str = "tank";
+
+fn default_player_materials() -> String {
+    DEFAULT_PLAYER_MATERIALS.to_string()
+}
+
+fn default_player_team() -> String {
+    DEFAULT_PLAYER_TEAM.to_string()
+}
+
+fn default_player_unit() -> String {
+    DEFAULT_PLAYER_UNIT.to_string()
+}
+
+// A unit placed in a level.
+#[derive(Clone)]
+pub struct UnitPlacement {
+    pub unit: UnitDefinition,
+    pub location: Transform,
+    /// Name of the unit's team, in the TeamPalette
+    pub team: String,
+}
 
-#[derive(Clone, Default)]
+#[derive(Clone)]
 pub struct Level {
+    /// e.g. for the loading screen
+    pub name: String,
+    /// Name of the SpriteGrid map, in the asset manifest
+    pub map: String,
+    /// (rows, columns) of the map, if known
+    pub map_size: Option<(usize, usize)>,
     player_location: Transform,
+    /// The player's kind of unit
+    pub player_unit: UnitDefinition,
+    /// Material overrides for the player's tank
+    pub player_material_rules: Vec<SerializedMaterialRule>,
+    /// Name of the player's team, in the TeamPalette
+    pub player_team: String,
+    /// Units besides the player's
+    pub enemies: Vec<UnitPlacement>,
+    /// Every asset the level needs, including its map
+    pub assets: BTreeSet<AssetKey>,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Level {
+            name: String::new(),
+            map: DEFAULT_MAP.to_string(),
+            map_size: None,
+            player_location: Transform::default(),
+            player_unit: UnitDefinition::default(),
+            player_material_rules: Vec::new(),
+            player_team: default_player_team(),
+            enemies: Vec::new(),
+            assets: BTreeSet::new(),
+        }
+    }
+}
+
+// The area of the ground plane covered by the map.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MapBounds {
+    pub min_x: f32,
+    pub max_x: f32,
+    pub min_z: f32,
+    pub max_z: f32,
+}
+
+impl MapBounds {
+    pub fn from_map_size(num_rows: usize, num_cols: usize) -> Self {
+        let (half_width, half_depth) = grid_half_extents(num_rows, num_cols);
+        MapBounds {
+            min_x: -half_width,
+            max_x: half_width,
+            min_z: -half_depth,
+            max_z: half_depth,
+        }
+    }
+
+    // The closest point within the bounds (ignoring Y).
+    pub fn clamp(&self, position: Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(
+            position.x.max(self.min_x).min(self.max_x),
+            position.y,
+            position.z.max(self.min_z).min(self.max_z),
+        )
+    }
+}
+
+// A unit placed in a level, as written in the level file.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct SerializedUnitPlacement {
+    /// Name of the unit's definition, in `assets/unit/`
+    pub unit: String,
+    /// Position on the ground plane, as (x, z)
+    pub position: (f32, f32),
+    /// Heading, in degrees
+    #[serde(default)]
+    pub rotation: f32,
+    pub team: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+struct SerializedLevel {
+    /// Name of the SpriteGrid map, in the asset manifest
+    pub map: String,
+    /// Player's starting position on the ground plane, as (x, z)
+    #[serde(default)]
+    pub player_position: (f32, f32),
+    /// Player's starting heading, in degrees
+    #[serde(default)]
+    pub player_rotation: f32,
+    /// Name of the player's unit definition, in `assets/unit/`
+    #[serde(default = "default_player_unit")]
+    pub player_unit: String,
+    /// Path of the player tank's material rules, relative to the assets directory
+    #[serde(default = "default_player_materials")]
+    pub player_materials: String,
+    /// Name of the player's team, in the team palette
+    #[serde(default = "default_player_team")]
+    pub player_team: String,
+    #[serde(default)]
+    pub enemies: Vec<SerializedUnitPlacement>,
+    /// Assets the level needs, besides its map, its units' and its teams'
+    #[serde(default)]
+    pub assets: AssetDependencies,
+}
+
+impl Level {
+    // Load a level file.
+    //
+    // The level's map is read from the assets directory to find its size.
+    pub fn load<P: AsRef<Path>>(
+        path: P,
+        assets_dir: &Path,
+        manifest: &AssetManifest,
+        palette: &TeamPalette,
+    ) -> Result<Level, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|err| {
+            Error::from_string(format!("Failed to open level file {}: {}", path.display(), err))
+        })?;
+        let load_data: SerializedLevel = from_ron_reader(file).map_err(|err| {
+            Error::from_string(format!("Failed to parse level file {}: {}", path.display(), err))
+        })?;
+
+        let player_location = ground_location(load_data.player_position, load_data.player_rotation);
+        let load_unit = |name: &str| -> Result<UnitDefinition, Error> {
+            let mut unit = UnitDefinition::load_named(assets_dir, name)?;
+            unit.resolve_collider(manifest)?;
+            unit.resolve_material_originals(manifest)?;
+            Ok(unit)
+        };
+        let player_unit = load_unit(&load_data.player_unit)?;
+        let enemies = load_data.enemies
+            .iter()
+            .map(|placement| {
+                Ok(UnitPlacement {
+                    unit: load_unit(&placement.unit)?,
+                    location: ground_location(placement.position, placement.rotation),
+                    team: placement.team.clone(),
+                })
+            })
+            .collect::<Result<Vec<UnitPlacement>, Error>>()?;
+
+        let map = manifest.grids.get(&load_data.map).ok_or_else(|| {
+            Error::from_string(format!(
+                "Level {} refers to unknown map '{}'", path.display(), load_data.map
+            ))
+        })?;
+        let map_size = Some(load_grid_dimensions(assets_dir.join(&map.path))?);
+        let mut player_material_rules =
+            load_material_rules(assets_dir.join(&load_data.player_materials))?;
+        let player_mesh = manifest.mesh_path(&player_unit.mesh)?;
+        for rule in &mut player_material_rules {
+            rule.resolve_originals(&player_mesh)?;
+        }
+
+        let mut dependencies = load_data.assets.clone();
+        dependencies.grids.push(load_data.map.clone());
+        dependencies.extend(&player_unit.dependencies());
+        for rule in &player_material_rules {
+            dependencies.materials.extend(rule.replacement.iter().cloned());
+        }
+        for enemy in &enemies {
+            dependencies.extend(&enemy.unit.dependencies());
+        }
+        // The teams' colours, and the units' hit flash.
+        let teams = Some(&load_data.player_team)
+            .into_iter()
+            .chain(enemies.iter().map(|enemy| &enemy.team));
+        for team in teams {
+            let material = palette.team(team).and_then(|colours| colours.material.as_ref());
+            if let Some(material) = material {
+                dependencies.materials.push(material.clone());
+            }
+        }
+        dependencies.materials.push(HIT_FLASH_MATERIAL.to_string());
+        let assets = manifest.resolve(&dependencies).map_err(|err| {
+            Error::from_string(format!("Level {} needs an {}", path.display(), err))
+        })?;
+
+        let name = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+
+        Ok(Level {
+            name,
+            map: load_data.map,
+            map_size,
+            player_location,
+            player_unit,
+            player_material_rules,
+            player_team: load_data.player_team,
+            enemies,
+            assets,
+        })
+    }
+
+    pub fn map_bounds(&self) -> Option<MapBounds> {
+        self.map_size
+            .map(|(num_rows, num_cols)| MapBounds::from_map_size(num_rows, num_cols))
+    }
+}
+
+// A transform on the ground plane, from a level file's (x, z) position and heading in degrees.
+fn ground_location((x, z): (f32, f32), rotation: f32) -> Transform {
+    let mut location = Transform::default();
+    location.set_translation_xyz(x, 0.0, z);
+    location.set_rotation_y_axis(rotation.to_radians());
+    location
 }
 
-pub fn init_level(world: &mut World, assets: Assets, level: &Level) -> () {
-    init_grid(world, assets.clone());
-    init_player(world, assets.clone(), level.player_location.clone());
-    init_camera(world);
+pub fn init_level(world: &mut World, registry: AssetRegistry, level: &Level) -> () {
+    init_grid(world, &registry, level);
+    init_spatial_grid(world, level.map_bounds());
+    let player = init_player(
+        world,
+        &level.player_unit,
+        level.player_location.clone(),
+        &level.player_material_rules,
+        Team::new(level.player_team.as_str()),
+    );
+    for enemy in &level.enemies {
+        spawn_unit(world, &enemy.unit, enemy.location.clone(), Team::new(enemy.team.as_str()));
+    }
+    init_camera(world, player, level.map_bounds());
     init_lighting(world);
 }
 
-fn init_grid(world: &mut World, assets: Assets) -> () {
-    init_map_grid(world, assets.clone());
+// Delete every entity created by `init_level`, and the level's UI (given by its roots).
+//
+// The Main state does only this when it stops, so the tests below cover it.
+pub fn teardown_level(world: &mut World, ui_roots: &[Entity]) -> () {
+    for ui_root in ui_roots {
+        delete_hierarchy(world, *ui_root);
+    }
+
+    let level_entities: Vec<Entity> = world.exec(
+        |(entities, level_entities): (Entities<'_>, ReadStorage<'_, LevelEntity>)| {
+            (&entities, &level_entities)
+                .join()
+                .map(|(entity, _)| entity)
+                .collect()
+        },
+    );
+
+    for entity in level_entities {
+        delete_hierarchy(world, entity);
+    }
 }
 
-pub fn init_map_grid(world: &mut World, assets: Assets) -> () {
+fn init_grid(world: &mut World, registry: &AssetRegistry, level: &Level) -> () {
+    match registry.grid(&level.map) {
+        Some(grid) => init_map_grid(world, grid),
+        None => error!("Map '{}' isn't loaded", level.map),
+    }
+}
+
+pub fn init_map_grid(world: &mut World, grid: GridAsset) -> () {
     let mut transform = Transform::default();
     transform.set_rotation_euler(-PI / 2.0, 0.0, 0.0);
 
     let _grid = world
         .create_entity()
         .with(transform)
-        .with(assets.map_sprite_sheet_material.clone())
-        .with(assets.sprite_grid.clone())
+        .with(grid.material)
+        .with(grid.mesh)
+        .with(LevelEntity)
         .build();
 }
 
-fn init_camera(world: &mut World) {
-    let position = Translation3::new(0.0, 15.0, 15.0);
-    let rotation = UnitQuaternion::from_euler_angles(
-        -PI / 4.0,
-        0.0,
-        0.0
-    );
+// The SpatialGrid's cells line up with the map's tiles.
+fn init_spatial_grid(world: &mut World, bounds: Option<MapBounds>) {
+    let origin = bounds.map_or_else(Vector2::zeros, |bounds| {
+        Vector2::new(bounds.min_x, bounds.min_z)
+    });
+    world.insert(SpatialGrid::new(origin, TILE_SIZE));
+}
+
+fn init_camera(world: &mut World, player: Entity, bounds: Option<MapBounds>) {
+    // Looking down at 45 degrees, from (0, 15, 15) relative to the player.
+    let controller = CameraController::default();
+    let offset = controller.offset();
+    let position = Translation3::from(offset);
+    let rotation = controller.rotation();
 
     let scale = Vector3::new(1.0, 1.0, 1.0);
     let transform = Transform::new(position, rotation, scale);
+
+    let aspect_ratio = world
+        .try_fetch::<ScreenDimensions>()
+        .map_or(DEFAULT_ASPECT_RATIO, |dimensions| dimensions.aspect_ratio());
     world
         .create_entity()
-        .with(Camera::from(Projection::perspective(
-          1.3,
-          PI / 3.0,
-          0.01,
-          1024.0,
-        )))
+        .with(Camera::from(perspective_projection(aspect_ratio)))
         .with(transform)
+        .with(CameraFollow::new(player, offset, rotation).with_bounds(bounds))
+        .with(controller)
+        .with(CameraShake::default())
+        .with(LevelEntity)
         .build();
 }
 
@@ -85,5 +397,178 @@ fn init_lighting(world: &mut World) {
     world
         .create_entity()
         .with(Light::Directional(direction_light))
+        .with(LevelEntity)
         .build();
 }
+
+#[cfg(test)]
+mod tests {
+    use amethyst::{
+        assets::Handle,
+        core::{
+            transform::{Parent, TransformBundle},
+            Named,
+            SystemBundle,
+        },
+        ecs::{Dispatcher, DispatcherBuilder},
+        renderer::{Material, Mesh},
+    };
+    use amethyst_gltf::GltfSceneAsset;
+
+    use super::*;
+    use crate::animation::AnimationController;
+    use crate::assets::AssetKind;
+    use crate::collision::Collider;
+    use crate::player::Player;
+    use crate::replace_material::ReplaceMaterial;
+    use crate::spatial_grid::SpatialIndexed;
+    use crate::turret::Turret;
+    use crate::unit::{Health, Movement, Weapon};
+
+    // Nodes spawned under each unit, as the glTF scene loader would.
+    const NODES_PER_UNIT: usize = 5;
+
+    // A world without a window or loaded assets,
+    //  whose ParentHierarchy is kept up to date by the TransformBundle's systems.
+    fn headless_world() -> (World, Dispatcher<'static, 'static>) {
+        let mut world = World::new();
+        let mut builder = DispatcherBuilder::new();
+        TransformBundle::new()
+            .build(&mut world, &mut builder)
+            .expect("TransformBundle builds");
+        let mut dispatcher = builder.build();
+        dispatcher.setup(&mut world);
+
+        world.register::<AnimationController>();
+        world.register::<Camera>();
+        world.register::<CameraController>();
+        world.register::<CameraFollow>();
+        world.register::<CameraShake>();
+        world.register::<Collider>();
+        world.register::<Handle<GltfSceneAsset>>();
+        world.register::<Handle<Material>>();
+        world.register::<Handle<Mesh>>();
+        world.register::<Health>();
+        world.register::<LevelEntity>();
+        world.register::<Light>();
+        world.register::<Movement>();
+        world.register::<Named>();
+        world.register::<Player>();
+        world.register::<ReplaceMaterial>();
+        world.register::<SpatialIndexed>();
+        world.register::<Team>();
+        world.register::<Turret>();
+        world.register::<Weapon>();
+        (world, dispatcher)
+    }
+
+    fn load_level(path: &Path) -> Level {
+        let assets_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        let manifest = AssetManifest::load(assets_dir.join("manifest.ron")).unwrap();
+        let palette = TeamPalette::load(assets_dir.join("team").join("palette.ron")).unwrap();
+        Level::load(path, &assets_dir, &manifest, &palette).unwrap()
+    }
+
+    fn basic_level() -> Level {
+        let assets_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("assets");
+        load_level(&assets_dir.join("level").join("basic.ron"))
+    }
+
+    fn update(world: &mut World, dispatcher: &mut Dispatcher<'_, '_>) -> () {
+        dispatcher.dispatch(world);
+        world.maintain();
+    }
+
+    fn entity_count(world: &World) -> usize {
+        world.entities().join().count()
+    }
+
+    // What the Main state does when it starts: init the level,
+    //  and create its UI; the units' glTF nodes are spawned afterwards.
+    fn start(world: &mut World, dispatcher: &mut Dispatcher<'_, '_>, level: &Level) -> Entity {
+        init_level(world, AssetRegistry::default(), level);
+
+        let units: Vec<Entity> = {
+            let (entities, healths) = (world.entities(), world.read_storage::<Health>());
+            (&entities, &healths).join().map(|(entity, _)| entity).collect()
+        };
+        for unit in units {
+            let mut parent = unit;
+            for _ in 0..NODES_PER_UNIT {
+                parent = world
+                    .create_entity()
+                    .with(Transform::default())
+                    .with(Parent { entity: parent })
+                    .build();
+            }
+        }
+
+        let ui_root = world.create_entity().build();
+        for _ in 0..3 {
+            world.create_entity().with(Parent { entity: ui_root }).build();
+        }
+        update(world, dispatcher);
+        ui_root
+    }
+
+    fn stop(world: &mut World, dispatcher: &mut Dispatcher<'_, '_>, ui_root: Entity) -> () {
+        teardown_level(world, &[ui_root]);
+        update(world, dispatcher);
+    }
+
+    #[test]
+    fn restarting_a_level_leaves_no_entities_behind() {
+        let (mut world, mut dispatcher) = headless_world();
+        let level = basic_level();
+        let before = entity_count(&world);
+
+        let ui_root = start(&mut world, &mut dispatcher, &level);
+        let started = entity_count(&world);
+        // The player and the enemy with their nodes, the camera, the light and the UI;
+        //  the map isn't loaded, so has no entity.
+        assert_eq!(started, before + 2 * (1 + NODES_PER_UNIT) + 2 + 4);
+
+        stop(&mut world, &mut dispatcher, ui_root);
+        assert_eq!(entity_count(&world), before);
+
+        let ui_root = start(&mut world, &mut dispatcher, &level);
+        assert_eq!(entity_count(&world), started);
+        stop(&mut world, &mut dispatcher, ui_root);
+        assert_eq!(entity_count(&world), before);
+    }
+
+    #[test]
+    fn the_teams_colours_and_the_hit_flash_are_loaded_with_the_level() {
+        let level = basic_level();
+        for material in &["tank_blue", HIT_FLASH_MATERIAL] {
+            let key = AssetKey::new(AssetKind::Material, *material);
+            assert!(level.assets.contains(&key), "level doesn't load {}", material);
+        }
+    }
+
+    #[test]
+    fn the_player_material_rules_replacements_are_loaded_with_the_level() {
+        // The red team has no material, so only the rule needs "tank_blue".
+        let dir = std::env::temp_dir().join(format!("level_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let rules_path = dir.join("player_tank.ron");
+        std::fs::write(
+            &rules_path,
+            r#"(rules: [(name: Some(Exact("TankTracks")), replacement: Some("tank_blue"))])"#,
+        ).unwrap();
+        let level_path = dir.join("level.ron");
+        std::fs::write(
+            &level_path,
+            format!(
+                r#"(map: "basic_map", player_team: "red", player_materials: {:?})"#,
+                rules_path.display().to_string(),
+            ),
+        ).unwrap();
+
+        let level = load_level(&level_path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(level.player_material_rules.len(), 1);
+        let key = AssetKey::new(AssetKind::Material, "tank_blue");
+        assert!(level.assets.contains(&key), "level doesn't load the rule's replacement");
+    }
+}
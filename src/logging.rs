@@ -0,0 +1,194 @@
+// Diagnostics, through the `log` crate.
+//
+// Each module logs with its module path as the target (the `log` default),
+//  and levels are set per target in `resources/logging.ron`.
+// The `ACTION_GAME_LOG` env var overrides them,
+//  e.g. `ACTION_GAME_LOG=warn,crate::replace_material=debug`.
+//
+// Recent messages are kept in a LogHistory, which the debug overlay shows.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use amethyst::prelude::Config;
+use log::{LevelFilter, Log, Metadata, Record};
+use serde::{Deserialize, Serialize};
+
+pub const LOG_ENV_VAR: &str = "ACTION_GAME_LOG";
+
+// Targets starting with this refer to this game's modules,
+//  whichever binary they're in (e.g. `crate::player`).
+const THIS_CRATE: &str = "crate";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    /// Level for targets which aren't listed in `targets`
+    pub level: LevelFilter,
+    /// Levels for targets (module paths) and their submodules
+    pub targets: BTreeMap<String, LevelFilter>,
+    /// How many recent messages the LogHistory keeps
+    pub history_length: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        let mut targets = BTreeMap::new();
+        targets.insert(String::from("gfx_backend_vulkan"), LevelFilter::Warn);
+        targets.insert(String::from("rendy"), LevelFilter::Error);
+        LoggingConfig {
+            level: LevelFilter::Info,
+            targets,
+            history_length: 100,
+        }
+    }
+}
+
+impl LoggingConfig {
+    // Load the config file (if it's there), then apply the ACTION_GAME_LOG env var.
+    //
+    // Problems are returned rather than logged, since logging hasn't started yet.
+    pub fn load_with_overrides<P: AsRef<Path>>(path: P) -> (LoggingConfig, Vec<String>) {
+        let path = path.as_ref();
+        let mut problems = Vec::new();
+
+        let mut config = if path.exists() {
+            LoggingConfig::load(path).unwrap_or_else(|err| {
+                problems.push(format!("Failed loading {}: {}", path.display(), err));
+                LoggingConfig::default()
+            })
+        } else {
+            LoggingConfig::default()
+        };
+
+        if let Ok(spec) = std::env::var(LOG_ENV_VAR) {
+            if let Err(err) = config.apply_overrides(&spec) {
+                problems.push(format!("Ignoring {}: {}", LOG_ENV_VAR, err));
+            }
+        }
+        (config, problems)
+    }
+
+    // Apply levels from e.g. the ACTION_GAME_LOG env var:
+    //  comma-separated `level`s (for every target) or `target=level`s.
+    pub fn apply_overrides(&mut self, spec: &str) -> Result<(), String> {
+        for directive in spec.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let parse_level = |level: &str| {
+                level.parse::<LevelFilter>()
+                    .map_err(|_| format!("invalid log level '{}'", level))
+            };
+            match directive.find('=') {
+                Some(index) => {
+                    let level = parse_level(&directive[index + 1..])?;
+                    self.targets.insert(directive[..index].to_string(), level);
+                }
+                None => self.level = parse_level(directive)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+// Recent log messages, shared between the logger and the game.
+#[derive(Clone, Default)]
+pub struct LogHistory {
+    lines: Arc<Mutex<VecDeque<String>>>,
+    capacity: usize,
+}
+
+impl LogHistory {
+    pub fn new(capacity: usize) -> Self {
+        LogHistory {
+            lines: Arc::new(Mutex::new(VecDeque::with_capacity(capacity))),
+            capacity,
+        }
+    }
+
+    fn push(&self, line: String) -> () {
+        if let Ok(mut lines) = self.lines.lock() {
+            if lines.len() >= self.capacity {
+                lines.pop_front();
+            }
+            lines.push_back(line);
+        }
+    }
+
+    // The most recent lines (up to `count`), oldest first.
+    pub fn recent(&self, count: usize) -> Vec<String> {
+        match self.lines.lock() {
+            Ok(lines) => lines.iter().skip(lines.len().saturating_sub(count)).cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+struct GameLogger {
+    level: LevelFilter,
+    /// Longest targets first, so the most specific one matches
+    targets: Vec<(String, LevelFilter)>,
+    history: LogHistory,
+}
+
+impl GameLogger {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.targets
+            .iter()
+            .find(|(prefix, _)| {
+                target.starts_with(prefix.as_str())
+                    && (target.len() == prefix.len() || target[prefix.len()..].starts_with("::"))
+            })
+            .map_or(self.level, |(_, level)| *level)
+    }
+}
+
+impl Log for GameLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!("[{}][{}] {}", record.level(), record.target(), record.args());
+        println!("{}", line);
+        self.history.push(line);
+    }
+
+    fn flush(&self) {}
+}
+
+// Install the logger (instead of `amethyst::start_logger`).
+//
+// Returns the history of messages, to add as a resource.
+pub fn start_logging(config: &LoggingConfig) -> LogHistory {
+    // e.g. "action_game_3d", or "simple_level".
+    let crate_name = module_path!().split("::").next().unwrap_or_default();
+    let mut targets: Vec<(String, LevelFilter)> = config.targets
+        .iter()
+        .map(|(target, level)| {
+            let target = if target == THIS_CRATE || target.starts_with("crate::") {
+                target.replacen(THIS_CRATE, crate_name, 1)
+            } else {
+                target.clone()
+            };
+            (target, *level)
+        })
+        .collect();
+    targets.sort_by_key(|(target, _)| std::cmp::Reverse(target.len()));
+
+    let max_level = targets.iter().map(|(_, level)| *level).fold(config.level, std::cmp::max);
+    let history = LogHistory::new(config.history_length);
+    let logger = GameLogger {
+        level: config.level,
+        targets,
+        history: history.clone(),
+    };
+
+    match log::set_boxed_logger(Box::new(logger)) {
+        Ok(()) => log::set_max_level(max_level),
+        Err(err) => eprintln!("Failed starting logger: {}", err),
+    }
+    history
+}
@@ -0,0 +1,261 @@
+// Command-line options shared by the game binaries.
+//
+// Each option overrides something which would otherwise be read from
+// the user's preferences, the `resources/` directory (or hard-coded in `main`).
+
+use std::path::{Path, PathBuf};
+
+use amethyst::{
+    prelude::Config,
+    window::{DisplayConfig, MonitorIdent},
+    winit::EventsLoop,
+    Error,
+};
+use err_derive::Error;
+
+use crate::preferences::{Preferences, user_input_config_path};
+
+const USAGE: &str = "\
+Options:
+    --level <path>          Level file to load (default: assets/level/basic.ron)
+    --skip-menu             Start loading the level instead of the main menu
+    --window <W>x<H>        Window dimensions, e.g. 1024x768
+    --fullscreen            Open the window fullscreen on the primary monitor
+    --input-config <path>   Input bindings file (default: the user's bindings,
+                            or resources/input.ron)
+    --assets-dir <path>     Assets directory (default: assets)
+    --help                  Print this message";
+
+#[derive(Debug, Error)]
+pub enum OptionsError {
+    #[error(display = "missing value for option {}", _0)]
+    MissingValue(String),
+    #[error(display = "invalid window dimensions '{}', expected e.g. 800x600", _0)]
+    InvalidDimensions(String),
+    #[error(display = "unknown option '{}'", _0)]
+    UnknownOption(String),
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    pub level: Option<PathBuf>,
+    pub skip_menu: bool,
+    pub window_dimensions: Option<(u32, u32)>,
+    pub fullscreen: bool,
+    pub input_config: Option<PathBuf>,
+    pub assets_dir: Option<PathBuf>,
+    /// Whether `--help` was given
+    pub help: bool,
+}
+
+fn parse_dimensions(value: &str) -> Option<(u32, u32)> {
+    let mut parts = value.splitn(2, |c| c == 'x' || c == 'X');
+    let width = parts.next()?.trim().parse().ok()?;
+    let height = parts.next()?.trim().parse().ok()?;
+    if width == 0 || height == 0 {
+        return None;
+    }
+    Some((width, height))
+}
+
+impl Options {
+    // Parse the options from the process' arguments.
+    //
+    // Prints usage and exits if `--help` is given.
+    pub fn from_args() -> Result<Options, OptionsError> {
+        let options = Options::parse(std::env::args().skip(1))?;
+        if options.help {
+            println!("{}", USAGE);
+            std::process::exit(0);
+        }
+        Ok(options)
+    }
+
+    pub fn parse<I>(args: I) -> Result<Options, OptionsError>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value_for = |option: &str| {
+                args.next()
+                    .ok_or_else(|| OptionsError::MissingValue(option.to_string()))
+            };
+
+            match arg.as_str() {
+                "--level" => {
+                    options.level = Some(PathBuf::from(value_for(&arg)?));
+                }
+                "--skip-menu" => {
+                    options.skip_menu = true;
+                }
+                "--window" => {
+                    let value = value_for(&arg)?;
+                    let dimensions = parse_dimensions(&value)
+                        .ok_or_else(|| OptionsError::InvalidDimensions(value))?;
+                    options.window_dimensions = Some(dimensions);
+                }
+                "--fullscreen" => {
+                    options.fullscreen = true;
+                }
+                "--input-config" => {
+                    options.input_config = Some(PathBuf::from(value_for(&arg)?));
+                }
+                "--assets-dir" => {
+                    options.assets_dir = Some(PathBuf::from(value_for(&arg)?));
+                }
+                "--help" | "-h" => {
+                    options.help = true;
+                }
+                other => {
+                    return Err(OptionsError::UnknownOption(other.to_string()));
+                }
+            }
+        }
+
+        Ok(options)
+    }
+
+    pub fn assets_dir(&self, app_root: &Path) -> PathBuf {
+        self.assets_dir
+            .clone()
+            .unwrap_or_else(|| app_root.join("assets"))
+    }
+
+    pub fn level_path(&self, assets_dir: &Path) -> PathBuf {
+        self.level
+            .clone()
+            .unwrap_or_else(|| assets_dir.join("level").join("basic.ron"))
+    }
+
+    pub fn input_config_path(&self, app_root: &Path) -> PathBuf {
+        self.input_config
+            .clone()
+            .or_else(|| user_input_config_path().filter(|path| path.exists()))
+            .unwrap_or_else(|| self.default_input_config_path(app_root))
+    }
+
+    // The bindings which the user's own bindings are reset to.
+    pub fn default_input_config_path(&self, app_root: &Path) -> PathBuf {
+        self.input_config
+            .clone()
+            .unwrap_or_else(|| app_root.join("resources").join("input.ron"))
+    }
+
+    // Load the `DisplayConfig` from the given path,
+    //  with the user's preferences and then the window options
+    //  applied on top of it.
+    pub fn display_config(
+        &self,
+        path: &Path,
+        preferences: &Preferences,
+    ) -> Result<DisplayConfig, Error> {
+        let mut display_config = DisplayConfig::load(path)?;
+
+        preferences.apply_to_display_config(&mut display_config);
+
+        if let Some(dimensions) = self.window_dimensions {
+            display_config.dimensions = Some(dimensions);
+        }
+
+        if self.fullscreen || preferences.fullscreen {
+            // MonitorIdent needs an EventsLoop to find the primary monitor;
+            //  the window itself isn't created until the RenderingBundle is built.
+            let events_loop = EventsLoop::new();
+            display_config.fullscreen =
+                Some(MonitorIdent::from_primary(&events_loop));
+        }
+
+        Ok(display_config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Options, OptionsError> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn dimensions_are_parsed() {
+        assert_eq!(parse_dimensions("800x600"), Some((800, 600)));
+        assert_eq!(parse_dimensions("1920X1080"), Some((1920, 1080)));
+        assert_eq!(parse_dimensions(" 1024 x 768 "), Some((1024, 768)));
+    }
+
+    #[test]
+    fn invalid_dimensions_are_rejected() {
+        let values = ["", "800", "800x", "x600", "0x600", "800x0", "-800x600", "800x600x2", "axb"];
+        for value in &values {
+            assert_eq!(parse_dimensions(value), None, "'{}' was accepted", value);
+        }
+    }
+
+    #[test]
+    fn no_arguments_give_the_defaults() {
+        let options = parse(&[]).unwrap();
+        assert_eq!(options.level, None);
+        assert!(!options.skip_menu);
+        assert_eq!(options.window_dimensions, None);
+        assert!(!options.fullscreen);
+        assert_eq!(options.input_config, None);
+        assert_eq!(options.assets_dir, None);
+        assert!(!options.help);
+    }
+
+    #[test]
+    fn every_option_is_parsed() {
+        let options = parse(&[
+            "--level", "level/test.ron",
+            "--skip-menu",
+            "--window", "1024x768",
+            "--fullscreen",
+            "--input-config", "my_input.ron",
+            "--assets-dir", "my_assets",
+            "--help",
+        ]).unwrap();
+        assert_eq!(options.level, Some(PathBuf::from("level/test.ron")));
+        assert!(options.skip_menu);
+        assert_eq!(options.window_dimensions, Some((1024, 768)));
+        assert!(options.fullscreen);
+        assert_eq!(options.input_config, Some(PathBuf::from("my_input.ron")));
+        assert_eq!(options.assets_dir, Some(PathBuf::from("my_assets")));
+        assert!(options.help);
+        assert!(parse(&["-h"]).unwrap().help);
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        match parse(&["--level"]) {
+            Err(OptionsError::MissingValue(option)) => assert_eq!(option, "--level"),
+            other => panic!("unexpected {:?}", other),
+        }
+        match parse(&["--window", "big"]) {
+            Err(OptionsError::InvalidDimensions(value)) => assert_eq!(value, "big"),
+            other => panic!("unexpected {:?}", other),
+        }
+        match parse(&["--skip-menu", "--verbose"]) {
+            Err(OptionsError::UnknownOption(option)) => assert_eq!(option, "--verbose"),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn paths_default_to_the_app_root() {
+        let options = Options::default();
+        let app_root = Path::new("game");
+        assert_eq!(options.assets_dir(app_root), PathBuf::from("game/assets"));
+        assert_eq!(
+            options.level_path(&options.assets_dir(app_root)),
+            PathBuf::from("game/assets/level/basic.ron"),
+        );
+        assert_eq!(
+            options.default_input_config_path(app_root),
+            PathBuf::from("game/resources/input.ron"),
+        );
+    }
+}
@@ -1,11 +1,5 @@
-use std::borrow::Cow;
-use std::collections::HashSet;
-
 use amethyst::{
-    core::{
-        transform::Transform,
-        Named,
-    },
+    core::transform::Transform,
     ecs::{NullStorage},
     ecs::prelude::{
         Component, Entity,
@@ -13,9 +7,10 @@ use amethyst::{
     prelude::*,
 };
 
-use crate::assets::Assets;
-use crate::replace_material::ReplaceMaterial;
-use crate::utils::print_gltf_info;
+use crate::assets::AssetRegistry;
+use crate::replace_material::{ReplaceMaterial, SerializedMaterialRule};
+use crate::team::Team;
+use crate::unit::{resolve_material_rules, spawn_unit, UnitDefinition};
 
 #[derive(Default)]
 pub struct Player;
@@ -24,34 +19,29 @@ impl Component for Player {
     type Storage = NullStorage<Self>;
 }
 
+// Spawn the player's unit.
+//
+// The level's `material_rules` for the player take precedence over the unit's own.
 pub fn init_player(
     world: &mut World,
-    assets: Assets,
-    init_location: Transform
+    unit: &UnitDefinition,
+    init_location: Transform,
+    material_rules: &[SerializedMaterialRule],
+    team: Team,
 ) -> Entity {
-    let mut tank_replace_material_targets: HashSet<Cow<'static, str>> =
-        HashSet::new();
-    tank_replace_material_targets.insert(Cow::Borrowed("TankBase"));
-    tank_replace_material_targets.insert(Cow::Borrowed("Turret"));
-    tank_replace_material_targets.insert(Cow::Borrowed("TurretGun"));
-
-    let replace_material = ReplaceMaterial {
-        targets: tank_replace_material_targets,
-        replacement: Some(assets.tank_blue_material.clone()),
-    };
-
-    let tank_gltf_mesh = assets.tank_gltf.clone();
-    {
-        let w : &mut World = world;
-        print_gltf_info(w, &tank_gltf_mesh);
+    let player = spawn_unit(world, unit, init_location, team);
+
+    let mut rules = world
+        .try_fetch::<AssetRegistry>()
+        .map(|registry| resolve_material_rules(material_rules, &registry))
+        .unwrap_or_default();
+    if let Some(replace_material) = world.write_storage::<ReplaceMaterial>().get_mut(player) {
+        rules.append(&mut replace_material.rules);
+        replace_material.rules = rules;
     }
-
     world
-        .create_entity()
-        .with(init_location)
-        .with(Named { name: Cow::Borrowed("player_tank_replace") })
-        .with(Player)
-        .with(replace_material)
-        .with(tank_gltf_mesh)
-        .build()
+        .write_storage::<Player>()
+        .insert(player, Player)
+        .expect("player was just spawned");
+    player
 }
@@ -0,0 +1,109 @@
+// User preferences, persisted in the platform's config directory.
+//
+// e.g. on Linux, `~/.config/amethyst-action-game-3d/preferences.ron`.
+//
+// The preferences override the defaults in `resources/display_config.ron`,
+//  and an `input.ron` in the same directory overrides `resources/input.ron`.
+
+use std::fs::{self, File};
+use std::path::PathBuf;
+
+use amethyst::{
+    error::Error,
+    window::DisplayConfig,
+};
+use log::warn;
+use ron::de::from_reader as from_ron_reader;
+use ron::ser::{to_string_pretty as to_ron_string_pretty, PrettyConfig};
+use serde::{Deserialize, Serialize,};
+
+use crate::controls::ControlScheme;
+
+static PREFERENCES_DIR_NAME: &str = "amethyst-action-game-3d";
+static PREFERENCES_FILE_NAME: &str = "preferences.ron";
+static INPUT_FILE_NAME: &str = "input.ron";
+
+/// Window resolutions which can be chosen in the settings menu
+pub const RESOLUTIONS: [(u32, u32); 5] = [
+    (800, 600),
+    (1024, 768),
+    (1280, 720),
+    (1600, 900),
+    (1920, 1080),
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Window dimensions; `None` uses the display config's dimensions
+    pub dimensions: Option<(u32, u32)>,
+    pub fullscreen: bool,
+    pub control_scheme: ControlScheme,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        Preferences {
+            dimensions: None,
+            fullscreen: false,
+            control_scheme: ControlScheme::default(),
+        }
+    }
+}
+
+pub fn preferences_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(PREFERENCES_DIR_NAME))
+}
+
+// Path of the user's input bindings,
+//  which are used in preference to `resources/input.ron`.
+pub fn user_input_config_path() -> Option<PathBuf> {
+    preferences_dir().map(|dir| dir.join(INPUT_FILE_NAME))
+}
+
+impl Preferences {
+    // Load the user's preferences,
+    //  falling back to the defaults if there aren't any (or they're broken).
+    pub fn load() -> Preferences {
+        let path = match preferences_dir() {
+            Some(dir) => dir.join(PREFERENCES_FILE_NAME),
+            None => return Preferences::default(),
+        };
+
+        if !path.exists() {
+            return Preferences::default();
+        }
+
+        let loaded = File::open(&path)
+            .map_err(|err| format!("{}", err))
+            .and_then(|file| {
+                from_ron_reader(file).map_err(|err| format!("{}", err))
+            });
+        match loaded {
+            Ok(preferences) => preferences,
+            Err(err) => {
+                warn!("Failed loading preferences from {}: {}", path.display(), err);
+                Preferences::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let dir = preferences_dir().ok_or_else(|| {
+            Error::from_string("no config directory to save preferences to")
+        })?;
+        fs::create_dir_all(&dir)?;
+
+        let serialized = to_ron_string_pretty(self, PrettyConfig::default())
+            .map_err(|_| Error::from_string("Failed to serialize preferences"))?;
+        fs::write(dir.join(PREFERENCES_FILE_NAME), serialized)?;
+
+        Ok(())
+    }
+
+    pub fn apply_to_display_config(&self, display_config: &mut DisplayConfig) -> () {
+        if let Some(dimensions) = self.dimensions {
+            display_config.dimensions = Some(dimensions);
+        }
+    }
+}
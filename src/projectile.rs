@@ -0,0 +1,34 @@
+use amethyst::{
+    ecs::DenseVecStorage,
+    ecs::prelude::{Component, Entity},
+};
+use nalgebra::Vector3;
+
+/// Units per second
+pub const SHELL_SPEED: f32 = 12.0;
+/// Seconds before a shell which hasn't hit anything disappears
+pub const SHELL_LIFETIME: f32 = 2.0;
+/// Radius of a shell's collider
+pub const SHELL_RADIUS: f32 = 0.1;
+/// Height above the ground which shells fly at
+pub const SHELL_HEIGHT: f32 = 0.8;
+
+// A shell fired by a unit's weapon.
+//
+// Spawned by the ProjectileSystem when a unit fires,
+//  and deleted when it hits another unit or its lifetime runs out.
+#[derive(Clone, Debug)]
+pub struct Projectile {
+    /// The unit which fired it (which it can't hit)
+    pub owner: Entity,
+    /// Units per second
+    pub velocity: Vector3<f32>,
+    /// Damage dealt to the unit it hits
+    pub damage: f32,
+    /// Seconds until it disappears
+    pub remaining: f32,
+}
+
+impl Component for Projectile {
+    type Storage = DenseVecStorage<Self>;
+}
@@ -1,19 +1,423 @@
-use std::borrow::Cow;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::fs::File;
+use std::path::Path;
 
 use amethyst::{
     assets::Handle,
     ecs::DenseVecStorage,
-    ecs::prelude::Component,
-    renderer::Material,
+    ecs::prelude::{Component, Entity},
+    error::Error,
+    renderer::{palette::Srgba, resources::Tint, Material},
 };
+use err_derive::Error;
+use ron::de::from_reader as from_ron_reader;
+use serde::{Deserialize, Serialize};
 
+use crate::assets::AssetRegistry;
+
+#[derive(Debug, Error)]
+pub enum MaterialRuleError {
+    #[error(display = "invalid glob pattern '{}': {}", _0, _1)]
+    InvalidGlob(String, String),
+    #[error(display = "invalid regex '{}': {}", _0, _1)]
+    InvalidRegex(String, String),
+    #[error(display = "unknown material '{}'", _0)]
+    UnknownMaterial(String),
+    #[error(display = "original material or texture hasn't been found in the glTF file")]
+    UnresolvedOriginal,
+}
+
+// How a node's name is matched by a MaterialRule.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(try_from = "SerializedNamePattern")]
+pub enum NamePattern {
+    Exact(String),
+    Glob(glob::Pattern),
+    Regex(regex::Regex),
+}
+
+// NamePattern as written in data files, before the pattern is compiled.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+enum SerializedNamePattern {
+    Exact(String),
+    Glob(String),
+    Regex(String),
+}
+
+impl TryFrom<SerializedNamePattern> for NamePattern {
+    type Error = MaterialRuleError;
+
+    fn try_from(pattern: SerializedNamePattern) -> Result<Self, Self::Error> {
+        match pattern {
+            SerializedNamePattern::Exact(name) => Ok(NamePattern::Exact(name)),
+            SerializedNamePattern::Glob(pattern) => glob::Pattern::new(&pattern)
+                .map(NamePattern::Glob)
+                .map_err(|err| MaterialRuleError::InvalidGlob(pattern, err.to_string())),
+            SerializedNamePattern::Regex(pattern) => regex::Regex::new(&pattern)
+                .map(NamePattern::Regex)
+                .map_err(|err| MaterialRuleError::InvalidRegex(pattern, err.to_string())),
+        }
+    }
+}
+
+impl NamePattern {
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            NamePattern::Exact(exact) => exact == name,
+            NamePattern::Glob(pattern) => pattern.matches(name),
+            NamePattern::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+// Replaces the material (and/or tints) every descendant node
+//  matching all of the given conditions.
+//
+// A rule without any conditions matches every descendant with a material.
+#[derive(Clone)]
+pub struct MaterialRule {
+    /// Match the node's name
+    pub name: Option<NamePattern>,
+    /// Match the nodes (by name) whose glTF material is the rule's original material,
+    ///  or uses its original texture
+    pub original_nodes: Option<HashSet<String>>,
+    pub replacement: Option<Handle<Material>>,
+    pub tint: Option<Tint>,
+}
+
+// MaterialRule as written in data files.
+//
+// The replacement is referred to by its name in the asset manifest.
+// The original material and texture are the glTF file's own
+//  (since the glTF scene loads its own copies of them),
+//  so are resolved to the nodes using them by `resolve_originals`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SerializedMaterialRule {
+    #[serde(default)]
+    pub name: Option<NamePattern>,
+    /// Name of a material in the glTF file
+    #[serde(default)]
+    pub original_material: Option<String>,
+    /// Name (or URI) of an image in the glTF file, used as a material's base colour texture
+    #[serde(default)]
+    pub original_texture: Option<String>,
+    /// The nodes matching the original material and texture, from `resolve_originals`
+    #[serde(skip)]
+    pub original_nodes: Option<HashSet<String>>,
+    #[serde(default)]
+    pub replacement: Option<String>,
+    /// (red, green, blue, alpha)
+    #[serde(default)]
+    pub tint: Option<(f32, f32, f32, f32)>,
+}
+
+impl SerializedMaterialRule {
+    // Find the nodes of a glTF file matching the rule's original material and texture.
+    pub fn resolve_originals<P: AsRef<Path>>(&mut self, gltf_path: P) -> Result<(), Error> {
+        if self.original_material.is_none() && self.original_texture.is_none() {
+            return Ok(());
+        }
+        let nodes = gltf_material_nodes(
+            gltf_path,
+            self.original_material.as_ref().map(String::as_str),
+            self.original_texture.as_ref().map(String::as_str),
+        )?;
+        self.original_nodes = Some(nodes);
+        Ok(())
+    }
+
+    pub fn resolve(&self, registry: &AssetRegistry) -> Result<MaterialRule, MaterialRuleError> {
+        let material = |name: &String| {
+            registry.material(name)
+                .ok_or_else(|| MaterialRuleError::UnknownMaterial(name.clone()))
+        };
+        let has_original = self.original_material.is_some() || self.original_texture.is_some();
+        if has_original && self.original_nodes.is_none() {
+            return Err(MaterialRuleError::UnresolvedOriginal);
+        }
+
+        Ok(MaterialRule {
+            name: self.name.clone(),
+            original_nodes: self.original_nodes.clone(),
+            replacement: self.replacement.as_ref().map(material).transpose()?,
+            tint: self.tint.map(|(r, g, b, a)| Tint(Srgba::new(r, g, b, a))),
+        })
+    }
+}
+
+// The names of a glTF file's nodes which have a mesh using the given material
+//  (by name), and/or whose material's base colour texture is the given image
+//  (by name or URI).
+pub fn gltf_material_nodes<P: AsRef<Path>>(
+    path: P,
+    material: Option<&str>,
+    texture: Option<&str>,
+) -> Result<HashSet<String>, Error> {
+    let path = path.as_ref();
+    let gltf = gltf::Gltf::open(path).map_err(|err| {
+        Error::from_string(format!("Failed to read glTF file {}: {}", path.display(), err))
+    })?;
+
+    let uses_texture = |primitive_material: &gltf::Material, texture: &str| {
+        let base_colour = primitive_material.pbr_metallic_roughness().base_color_texture();
+        base_colour.map_or(false, |info| {
+            let image = info.texture().source();
+            let uri = match image.source() {
+                gltf::image::Source::Uri { uri, .. } => Some(uri),
+                gltf::image::Source::View { .. } => None,
+            };
+            image.name() == Some(texture) || uri == Some(texture)
+        })
+    };
+
+    Ok(gltf
+        .nodes()
+        .filter(|node| {
+            node.mesh().map_or(false, |mesh| {
+                mesh.primitives().any(|primitive| {
+                    let primitive_material = primitive.material();
+                    material.map_or(true, |name| primitive_material.name() == Some(name))
+                        && texture.map_or(true, |name| uses_texture(&primitive_material, name))
+                })
+            })
+        })
+        .filter_map(|node| node.name().map(String::from))
+        .collect())
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct SerializedMaterialRules {
+    rules: Vec<SerializedMaterialRule>,
+}
+
+// Load a file of material rules, e.g. `assets/material/player_tank.ron`.
+pub fn load_material_rules<P: AsRef<Path>>(
+    path: P
+) -> Result<Vec<SerializedMaterialRule>, Error> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|_| {
+        Error::from_string(format!("Failed to open material rules file {}", path.display()))
+    })?;
+    let rules: SerializedMaterialRules = from_ron_reader(file).map_err(|err| {
+        Error::from_string(format!(
+            "Failed to parse material rules file {}: {}", path.display(), err
+        ))
+    })?;
+    Ok(rules.rules)
+}
+
+// Name of the material (in the asset manifest) which damaged units flash with.
+pub const HIT_FLASH_MATERIAL: &str = "flash";
+
+// A temporary change to the look of every node a ReplaceMaterial has matched
+//  (e.g. a hit flash, or a selection highlight).
+//
+// Overrides stack: the one with the highest priority is shown,
+//  and the most recently added one wins a tie.
+#[derive(Clone)]
+pub struct MaterialOverride {
+    /// Identifies the override, so that it can be removed (or replaced)
+    pub source: String,
+    pub priority: i32,
+    pub material: Option<Handle<Material>>,
+    pub tint: Option<Tint>,
+    /// Seconds until the override is removed; `None` lasts until it's removed
+    pub remaining: Option<f32>,
+}
+
+impl MaterialOverride {
+    pub fn new<S: Into<String>>(source: S, priority: i32) -> Self {
+        MaterialOverride {
+            source: source.into(),
+            priority,
+            material: None,
+            tint: None,
+            remaining: None,
+        }
+    }
+
+    pub fn with_material(mut self, material: Handle<Material>) -> Self {
+        self.material = Some(material);
+        self
+    }
+
+    pub fn with_tint(mut self, tint: Tint) -> Self {
+        self.tint = Some(tint);
+        self
+    }
+
+    pub fn with_duration(mut self, seconds: f32) -> Self {
+        self.remaining = Some(seconds);
+        self
+    }
+}
+
+// A node's material and tint.
+#[derive(Clone)]
+pub struct Appearance {
+    pub material: Handle<Material>,
+    pub tint: Option<Tint>,
+}
+
+// A descendant matched by a ReplaceMaterial.
+pub struct ReplacedNode {
+    /// The node's look before any rules or overrides, which reverting restores
+    pub original: Appearance,
+    /// The matching rule's result, or the original if no rule matched
+    pub base: Appearance,
+}
+
+// Overrides the materials of this entity's descendants (e.g. a glTF scene's nodes).
+//
+// The team's rules and then the entity's own rules are checked in order,
+//  and the first matching rule is applied.
+// Rules keep applying to descendants spawned later.
+//
+// The descendants' original materials are remembered,
+//  so changing the rules (or reverting) starts from them.
 #[derive(Default)]
 pub struct ReplaceMaterial {
-    pub targets: HashSet<Cow<'static, str>>,
-    pub replacement: Option<Handle<Material>>,
+    pub rules: Vec<MaterialRule>,
+    /// Set by the TeamColourSystem, from the entity's Team
+    pub team_rules: Vec<MaterialRule>,
+    /// The team which `team_rules` are for
+    pub team: Option<String>,
+    /// Set by `revert`, so that the TeamColourSystem doesn't re-apply the team's rules
+    pub team_reverted: bool,
+    /// In the order they were added
+    pub overrides: Vec<MaterialOverride>,
+    /// Descendants which have been matched already
+    pub replaced: HashMap<Entity, ReplacedNode>,
+    /// Set when the rules have changed, so descendants must be re-matched
+    pub rules_changed: bool,
+    /// Set when the descendants' look must be updated
+    pub dirty: bool,
+    /// Whether the descendants which existed when this was added have been matched;
+    ///  later ones are matched as they're spawned
+    pub descendants_scanned: bool,
+}
+
+impl ReplaceMaterial {
+    pub fn new(rules: Vec<MaterialRule>) -> Self {
+        ReplaceMaterial {
+            rules,
+            ..ReplaceMaterial::default()
+        }
+    }
+
+    // Use a team's rules instead of the current ones,
+    //  re-matching every descendant.
+    pub fn set_team_rules(&mut self, team: &str, rules: Vec<MaterialRule>) -> () {
+        self.team = Some(team.to_string());
+        self.team_reverted = false;
+        self.team_rules = rules;
+        self.rules_changed = true;
+    }
+
+    pub fn all_rules(&self) -> impl Iterator<Item = &MaterialRule> {
+        self.team_rules.iter().chain(self.rules.iter())
+    }
+
+    // Add an override, replacing any other from the same source.
+    pub fn push_override(&mut self, material_override: MaterialOverride) -> () {
+        self.overrides.retain(|existing| existing.source != material_override.source);
+        self.overrides.push(material_override);
+        self.dirty = true;
+    }
+
+    pub fn remove_override(&mut self, source: &str) -> () {
+        let count = self.overrides.len();
+        self.overrides.retain(|existing| existing.source != source);
+        self.dirty |= self.overrides.len() != count;
+    }
+
+    // Count down the timed overrides, removing the ones which have expired.
+    pub fn update_overrides(&mut self, dt: f32) -> () {
+        let count = self.overrides.len();
+        for material_override in &mut self.overrides {
+            if let Some(remaining) = &mut material_override.remaining {
+                *remaining -= dt;
+            }
+        }
+        self.overrides.retain(|o| o.remaining.map_or(true, |remaining| remaining > 0.0));
+        self.dirty |= self.overrides.len() != count;
+    }
+
+    // The override which is shown.
+    pub fn top_override(&self) -> Option<&MaterialOverride> {
+        // max_by_key returns the last of equal elements, i.e. the most recent.
+        self.overrides.iter().max_by_key(|o| o.priority)
+    }
+
+    // Remove every rule and override, restoring the descendants' original look.
+    //
+    // This includes the team's colours: the TeamColourSystem leaves them removed
+    //  (even if the entity's Team changes) until `set_team_rules` is called.
+    pub fn revert(&mut self) -> () {
+        self.rules.clear();
+        self.team_rules.clear();
+        self.team = None;
+        self.team_reverted = true;
+        self.overrides.clear();
+        self.rules_changed = true;
+    }
 }
 
 impl Component for ReplaceMaterial {
     type Storage = DenseVecStorage<Self>;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tank_gltf() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("assets/mesh/tank.gltf")
+    }
+
+    fn tank_nodes(material: Option<&str>, texture: Option<&str>) -> Vec<String> {
+        let mut nodes: Vec<String> = gltf_material_nodes(tank_gltf(), material, texture)
+            .expect("tank.gltf loads")
+            .into_iter()
+            .collect();
+        nodes.sort();
+        nodes
+    }
+
+    #[test]
+    fn nodes_are_found_by_their_gltf_material() {
+        assert_eq!(
+            tank_nodes(Some("TankOtherMaterial"), None),
+            vec!["TankTracks", "TurretGunMuzzle"],
+        );
+        assert_eq!(tank_nodes(Some("TankMaterial"), None), vec!["TankBase", "Turret", "TurretGun"]);
+        assert!(tank_nodes(Some("NoSuchMaterial"), None).is_empty());
+    }
+
+    #[test]
+    fn nodes_are_found_by_their_texture() {
+        assert_eq!(tank_nodes(None, Some("tank_texture")), vec!["TankBase", "Turret", "TurretGun"]);
+        assert_eq!(
+            tank_nodes(None, Some("tank_other_texture.png")),
+            vec!["TankTracks", "TurretGunMuzzle"],
+        );
+    }
+
+    #[test]
+    fn nodes_must_match_both_material_and_texture() {
+        assert!(tank_nodes(Some("TankMaterial"), Some("tank_other_texture")).is_empty());
+    }
+
+    #[test]
+    fn rules_must_resolve_their_originals() {
+        let mut rule: SerializedMaterialRule =
+            ron::de::from_str("(original_material: Some(\"TankOtherMaterial\"))").unwrap();
+        assert!(rule.resolve(&AssetRegistry::default()).is_err());
+
+        rule.resolve_originals(tank_gltf()).unwrap();
+        let resolved = rule.resolve(&AssetRegistry::default()).unwrap();
+        let nodes = resolved.original_nodes.unwrap();
+        assert!(nodes.contains("TankTracks") && !nodes.contains("TankBase"));
+    }
+}
@@ -1,8 +1,9 @@
 use amethyst;
 use amethyst::{
-    core::transform::TransformBundle,
+    animation::{AnimationBundle, VertexSkinningBundle},
+    core::{transform::{Transform, TransformBundle}, SystemExt},
     input::{InputBundle, StringBindings},
-    prelude::{Application, GameDataBuilder},
+    prelude::{Application, Config, GameDataBuilder},
     renderer::{
         plugins::{RenderDebugLines, RenderPbr3D, RenderToWindow},
         types::DefaultBackend,
@@ -16,37 +17,93 @@ use amethyst::{
     Error,
 };
 use amethyst_gltf::GltfSceneLoaderSystemDesc;
+use log::warn;
 
 use crate::systems::{
+    AnimationControllerSystem,
+    CameraAspectSystem,
+    CameraControlSystem,
+    CameraFollowSystem,
+    CameraShakeSystemDesc,
+    CollisionSystem,
+    DamageFlashSystemDesc,
     DebugSystem,
+    FireAnimationSystemDesc,
+    HealthSystemDesc,
     MovementSystem,
-    ReplaceMaterialSystem,
+    PlayerFireSystem,
+    ProjectileSystemDesc,
+    ReplaceMaterialSystemDesc,
+    SpatialGridSystem,
+    TeamColourSystem,
+    TurretSystem,
     UISystem,
 };
+use crate::assets::AssetManifest;
+use crate::controls::ControlsConfig;
+use crate::level::Level;
+use crate::logging::{start_logging, LoggingConfig};
+use crate::options::Options;
+use crate::preferences::Preferences;
 use crate::states::{
+    GameplayState,
     Loading,
 };
+use crate::team::TeamPalette;
 
+mod animation;
 mod assets;
+mod camera;
+mod collision;
+mod controls;
+mod events;
+mod key_bindings;
 mod level;
+mod logging;
+mod options;
 mod player;
+mod preferences;
+mod projectile;
 mod replace_material;
+mod spatial_grid;
 mod sprite_grid;
 mod states;
 mod systems;
+mod team;
+mod turret;
+mod unit;
 mod utils;
 
 fn main() -> Result<(), Error> {
-    amethyst::start_logger(Default::default());
+    let options = Options::from_args()?;
 
     let app_root = application_root_dir()?;
 
+    let logging_config_path = app_root.join("resources").join("logging.ron");
+    let (logging_config, logging_problems) =
+        LoggingConfig::load_with_overrides(&logging_config_path);
+    let log_history = start_logging(&logging_config);
+    for problem in logging_problems {
+        warn!("{}", problem);
+    }
+
     // Add our meshes directory to the asset loader.
-    let resources_directory = app_root.join("assets");
+    let resources_directory = options.assets_dir(&app_root);
 
     let display_config_path = app_root.join("resources").join("display_config.ron");
+    let preferences = Preferences::load();
+    let display_config =
+        options.display_config(&display_config_path, &preferences)?;
+
+    let input_config_path = options.input_config_path(&app_root);
 
-    let input_config_path = app_root.join("resources").join("input.ron");
+    let controls_config_path = app_root.join("resources").join("controls.ron");
+    let controls_config = ControlsConfig::load(&controls_config_path)?;
+
+    let level_path = options.level_path(&resources_directory);
+    let asset_manifest = AssetManifest::load(resources_directory.join("manifest.ron"))?;
+    let team_palette = TeamPalette::load(resources_directory.join("team").join("palette.ron"))?;
+    let level = Level::load(&level_path, &resources_directory, &asset_manifest, &team_palette)?;
 
     let game_data = GameDataBuilder::default()
         .with_bundle(
@@ -58,28 +115,114 @@ fn main() -> Result<(), Error> {
             "gltf_loader",
             &[]
         )
-        .with_system_desc(MovementSystem, "movement", &[])
+        .with(
+            MovementSystem.pausable(GameplayState::Running),
+            "movement",
+            &[]
+        )
+        .with(
+            TurretSystem.pausable(GameplayState::Running),
+            "turret",
+            &[]
+        )
+        .with(
+            PlayerFireSystem::default().pausable(GameplayState::Running),
+            "player_fire",
+            &[]
+        )
+        .with_system_desc(
+            ProjectileSystemDesc::default(),
+            "projectile",
+            &["player_fire"]
+        )
+        .with(
+            SpatialGridSystem.pausable(GameplayState::Running),
+            "spatial_grid",
+            &["movement", "projectile"]
+        )
+        .with(
+            CollisionSystem.pausable(GameplayState::Running),
+            "collision",
+            &["spatial_grid"]
+        )
+        .with(
+            CameraControlSystem::default().pausable(GameplayState::Running),
+            "camera_control",
+            &[]
+        )
+        .with_system_desc(
+            CameraFollowSystem::default(),
+            "camera_follow",
+            &["movement", "camera_control"]
+        )
+        .with_system_desc(
+            CameraShakeSystemDesc::default(),
+            "camera_shake",
+            &["camera_follow", "player_fire"]
+        )
+        .with_system_desc(CameraAspectSystem::default(), "camera_aspect", &[])
         .with_system_desc(UISystem::default(), "game_ui_system", &[])
         .with_system_desc(DebugSystem::default(), "game_debug_system", &[])
-        .with_system_desc(ReplaceMaterialSystem::default(), "replace_material_system", &[])
-        .with_bundle(TransformBundle::new().with_dep(&[]))?
+        .with_system_desc(TeamColourSystem::default(), "team_colour", &[])
+        .with_system_desc(DamageFlashSystemDesc::default(), "damage_flash", &[])
+        .with_system_desc(HealthSystemDesc::default(), "health", &[])
+        .with_system_desc(
+            ReplaceMaterialSystemDesc::default(),
+            "replace_material_system",
+            &["team_colour", "damage_flash"]
+        )
+        .with_system_desc(
+            FireAnimationSystemDesc::default(),
+            "fire_animation",
+            &["player_fire"]
+        )
+        .with_system_desc(
+            AnimationControllerSystem::default(),
+            "animation_controller",
+            &["fire_animation"]
+        )
+        .with_bundle(
+            AnimationBundle::<usize, Transform>::new(
+                "animation_control_system",
+                "sampler_interpolation_system",
+            )
+            .with_dep(&["animation_controller"])
+        )?
+        .with_bundle(
+            TransformBundle::new()
+                .with_dep(&["animation_control_system", "sampler_interpolation_system"])
+        )?
+        .with_bundle(
+            VertexSkinningBundle::new().with_dep(&[
+                "transform_system",
+                "animation_control_system",
+                "sampler_interpolation_system",
+            ])
+        )?
         .with_bundle(UiBundle::<StringBindings>::new())?
         .with_bundle(FpsCounterBundle::default())?
         .with_bundle(
             RenderingBundle::<DefaultBackend>::new()
                 // The RenderToWindow plugin provides all the scaffolding for opening a window and drawing on it
                 .with_plugin(
-                    RenderToWindow::from_config_path(display_config_path)?
+                    RenderToWindow::from_config(display_config)
                         .with_clear([0.0, 0.0, 0.0, 1.0]),
                 )
-                .with_plugin(RenderPbr3D::default())
+                .with_plugin(RenderPbr3D::default().with_skinning())
                 .with_plugin(RenderDebugLines::default())
                 .with_plugin(RenderUi::default())
         )?;
 
-    let init_state = Loading::default();
+    // This binary always skips the main menu.
+    let init_state = Loading::new(level);
 
     let mut game = Application::build(resources_directory, init_state)?
+        .with_resource(preferences)
+        .with_resource(log_history)
+        .with_resource(controls_config)
+        .with_resource(team_palette)
+        .with_resource(asset_manifest)
+        .with_resource(options)
         .build(game_data)?;
 
     game.run();
@@ -0,0 +1,564 @@
+// Where entities are on the ground plane, for finding the entities near a point
+//  (e.g. for targeting, splash damage or pickups)
+//  without every system joining over every Transform.
+//
+// Also the collisions' broad phase: colliders can only overlap if their bounds share a cell.
+//
+// Positions on the ground plane are (x, z) in a Vector2.
+
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use amethyst::{
+    ecs::NullStorage,
+    ecs::prelude::{Component, Entity},
+};
+use nalgebra::Vector2;
+
+use crate::sprite_grid::TILE_SIZE;
+
+// Marks entities (e.g. units) which the SpatialGrid indexes.
+//
+// Entities with a Collider are indexed too.
+// Only their own Transform is used, so they shouldn't have a Parent.
+#[derive(Default)]
+pub struct SpatialIndexed;
+
+impl Component for SpatialIndexed {
+    type Storage = NullStorage<Self>;
+}
+
+// An entity found by `SpatialGrid::raycast`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub entity: Entity,
+    /// Along the ray, from its origin
+    pub distance: f32,
+}
+
+// A uniform grid of cells over the ground plane,
+//  listing the entities in each cell (and the colliders' bounds touching each cell).
+//
+// Rebuilt each frame (by the SpatialGridSystem) from the indexed entities' Transforms,
+//  before the CollisionSystem pushes overlapping units apart.
+// A level's grid has cells lining up with its map's tiles;
+//  entities off the map are still indexed.
+pub struct SpatialGrid {
+    /// A corner of the cell (0, 0)
+    origin: Vector2<f32>,
+    cell_size: f32,
+    /// Each entity's position
+    positions: Vec<(Entity, Vector2<f32>)>,
+    /// Indices into `positions`, by the cell each is in
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    /// Indices into `positions`, by the cells each entity's bounds touch
+    bounds_cells: HashMap<(i32, i32), Vec<usize>>,
+}
+
+impl Default for SpatialGrid {
+    fn default() -> Self {
+        SpatialGrid::new(Vector2::zeros(), TILE_SIZE)
+    }
+}
+
+fn is_finite(vector: &Vector2<f32>) -> bool {
+    vector.x.is_finite() && vector.y.is_finite()
+}
+
+impl SpatialGrid {
+    pub fn new(origin: Vector2<f32>, cell_size: f32) -> Self {
+        SpatialGrid {
+            origin,
+            cell_size,
+            positions: Vec::new(),
+            cells: HashMap::new(),
+            bounds_cells: HashMap::new(),
+        }
+    }
+
+    // Remove every entity, keeping the cells' layout.
+    pub fn clear(&mut self) {
+        self.positions.clear();
+        for indices in self.cells.values_mut().chain(self.bounds_cells.values_mut()) {
+            indices.clear();
+        }
+    }
+
+    // Index an entity at a position.
+    //
+    // Positions which aren't finite (e.g. NaN) are ignored.
+    pub fn insert(&mut self, entity: Entity, position: Vector2<f32>) {
+        if !is_finite(&position) {
+            return;
+        }
+        let index = self.positions.len();
+        self.positions.push((entity, position));
+        let cell = self.cell(&position);
+        self.cells.entry(cell).or_default().push(index);
+    }
+
+    // Index an entity at a position, with (collider) bounds `(min, max)`,
+    //  for `candidate_pairs`.
+    pub fn insert_with_bounds(
+        &mut self,
+        entity: Entity,
+        position: Vector2<f32>,
+        (min, max): (Vector2<f32>, Vector2<f32>),
+    ) {
+        if !is_finite(&position) {
+            return;
+        }
+        self.insert(entity, position);
+        if !is_finite(&min) || !is_finite(&max) {
+            return;
+        }
+        let index = self.positions.len() - 1;
+        let ((min_x, min_z), (max_x, max_z)) = (self.cell(&min), self.cell(&max));
+        for x in min_x..=max_x {
+            for z in min_z..=max_z {
+                self.bounds_cells.entry((x, z)).or_default().push(index);
+            }
+        }
+    }
+
+    // How many entities are indexed.
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    pub fn cell(&self, position: &Vector2<f32>) -> (i32, i32) {
+        let local = (position - self.origin) / self.cell_size;
+        (local.x.floor() as i32, local.y.floor() as i32)
+    }
+
+    // The entities (and their positions) in a cell.
+    fn in_cell(&self, cell: (i32, i32)) -> impl Iterator<Item = &(Entity, Vector2<f32>)> {
+        self.cells
+            .get(&cell)
+            .into_iter()
+            .flatten()
+            .map(move |index| &self.positions[*index])
+    }
+
+    // The entities (and their positions) in the cells from `min` to `max` (inclusive).
+    fn in_cells(
+        &self,
+        (min_x, min_z): (i32, i32),
+        (max_x, max_z): (i32, i32),
+    ) -> impl Iterator<Item = &(Entity, Vector2<f32>)> {
+        (min_x..=max_x)
+            .flat_map(move |x| (min_z..=max_z).map(move |z| (x, z)))
+            .flat_map(move |cell| self.in_cell(cell))
+    }
+
+    // Entities within `radius` of `centre`.
+    pub fn within_radius(&self, centre: &Vector2<f32>, radius: f32) -> Vec<Entity> {
+        let extent = Vector2::new(radius, radius);
+        let min = self.cell(&(centre - extent));
+        let max = self.cell(&(centre + extent));
+        self.in_cells(min, max)
+            .filter(|(_, position)| (position - centre).norm_squared() <= radius * radius)
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    // Entities within the rectangle between the corners `min` and `max`.
+    pub fn within_rect(&self, min: &Vector2<f32>, max: &Vector2<f32>) -> Vec<Entity> {
+        self.in_cells(self.cell(min), self.cell(max))
+            .filter(|(_, position)| {
+                position.x >= min.x && position.x <= max.x
+                    && position.y >= min.y && position.y <= max.y
+            })
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    // The lowest and highest cells with entities in them, if any.
+    fn occupied_cells(&self) -> Option<((i32, i32), (i32, i32))> {
+        self.cells
+            .iter()
+            .filter(|(_, indices)| !indices.is_empty())
+            .map(|(cell, _)| *cell)
+            .fold(None, |bounds, (x, z)| match bounds {
+                None => Some(((x, z), (x, z))),
+                Some(((min_x, min_z), (max_x, max_z))) => {
+                    Some(((min_x.min(x), min_z.min(z)), (max_x.max(x), max_z.max(z))))
+                }
+            })
+    }
+
+    // Entities within `radius` of the ray from `origin` along `direction`
+    //  (up to `max_distance`), nearest first.
+    //
+    // Only the cells which the ray passes through (and those within `radius` of them)
+    //  are searched, stepping from cell to cell along the ray.
+    // The ray is clipped to the occupied cells first,
+    //  so a long ray takes no more steps than one across them.
+    pub fn raycast(
+        &self,
+        origin: &Vector2<f32>,
+        direction: &Vector2<f32>,
+        max_distance: f32,
+        radius: f32,
+    ) -> Vec<RayHit> {
+        if !is_finite(origin) || !max_distance.is_finite() || !radius.is_finite() {
+            return Vec::new();
+        }
+        let direction = match direction.try_normalize(f32::EPSILON) {
+            Some(direction) if is_finite(&direction) => direction,
+            _ => return Vec::new(),
+        };
+        let reach = (radius / self.cell_size).ceil() as i32;
+
+        // Where the ray enters and leaves the occupied cells, and those within reach of them.
+        let (min_cell, max_cell) = match self.occupied_cells() {
+            Some(bounds) => bounds,
+            None => return Vec::new(),
+        };
+        let corner = |(x, z): (i32, i32)| {
+            self.origin + Vector2::new(x as f32, z as f32) * self.cell_size
+        };
+        let min_corner = corner((min_cell.0 - reach, min_cell.1 - reach));
+        let max_corner = corner((max_cell.0 + reach + 1, max_cell.1 + reach + 1));
+        let mut enter = 0.0f32;
+        let mut exit = max_distance;
+        for axis in 0..2 {
+            if direction[axis] == 0.0 {
+                if origin[axis] < min_corner[axis] || origin[axis] > max_corner[axis] {
+                    return Vec::new();
+                }
+                continue;
+            }
+            let to_min = (min_corner[axis] - origin[axis]) / direction[axis];
+            let to_max = (max_corner[axis] - origin[axis]) / direction[axis];
+            enter = enter.max(to_min.min(to_max));
+            exit = exit.min(to_min.max(to_max));
+        }
+        if enter > exit {
+            return Vec::new();
+        }
+        // Stepping starts where the ray enters them (kept within them despite rounding,
+        //  e.g. when starting far away), and is measured from there.
+        let start = origin + direction * enter;
+        let start = Vector2::new(
+            start.x.max(min_corner.x).min(max_corner.x),
+            start.y.max(min_corner.y).min(max_corner.y),
+        );
+        let limit = exit - enter;
+        let (min_cell, max_cell) = (
+            (min_cell.0 - reach, min_cell.1 - reach),
+            (max_cell.0 + reach, max_cell.1 + reach),
+        );
+
+        // The direction to step in, the distance along the ray to the first cell boundary,
+        //  and the distance between boundaries, for one axis.
+        let axis_steps = |local: f32, cell: i32, direction: f32| -> (i32, f32, f32) {
+            if direction > 0.0 {
+                let next = (cell + 1) as f32 - local;
+                (1, next * self.cell_size / direction, self.cell_size / direction)
+            } else if direction < 0.0 {
+                let next = local - cell as f32;
+                (-1, next * self.cell_size / -direction, self.cell_size / -direction)
+            } else {
+                (0, f32::INFINITY, f32::INFINITY)
+            }
+        };
+        let local = (start - self.origin) / self.cell_size;
+        let mut cell = self.cell(&start);
+        let (step_x, mut next_x, delta_x) = axis_steps(local.x, cell.0, direction.x);
+        let (step_z, mut next_z, delta_z) = axis_steps(local.y, cell.1, direction.y);
+
+        let mut searched = HashSet::new();
+        let mut hits = Vec::new();
+        loop {
+            for x in (cell.0 - reach)..=(cell.0 + reach) {
+                for z in (cell.1 - reach)..=(cell.1 + reach) {
+                    if !searched.insert((x, z)) {
+                        continue;
+                    }
+                    for (entity, position) in self.in_cell((x, z)) {
+                        let offset = position - origin;
+                        let distance = offset.dot(&direction);
+                        if distance < 0.0 || distance > max_distance {
+                            continue;
+                        }
+                        if (offset - direction * distance).norm_squared() <= radius * radius {
+                            hits.push(RayHit { entity: *entity, distance });
+                        }
+                    }
+                }
+            }
+
+            if next_x.min(next_z) > limit {
+                break;
+            }
+            if next_x < next_z {
+                cell.0 += step_x;
+                next_x += delta_x;
+            } else {
+                cell.1 += step_z;
+                next_z += delta_z;
+            }
+            // Past the occupied cells, in the direction it's heading.
+            let leaving = |cell: i32, step: i32, min: i32, max: i32| {
+                (step > 0 && cell > max) || (step < 0 && cell < min)
+            };
+            if leaving(cell.0, step_x, min_cell.0, max_cell.0)
+                || leaving(cell.1, step_z, min_cell.1, max_cell.1)
+            {
+                break;
+            }
+        }
+
+        hits.sort_by(|a, b| a.distance.partial_cmp(&b.distance).unwrap_or(Ordering::Equal));
+        hits
+    }
+
+    // Pairs of entities indexed with bounds, whose bounds share a cell.
+    //
+    // Each pair is listed once, in the order the entities were inserted.
+    pub fn candidate_pairs(&self) -> Vec<(Entity, Entity)> {
+        let mut pairs = BTreeSet::new();
+        for indices in self.bounds_cells.values() {
+            for (i, a) in indices.iter().enumerate() {
+                for b in &indices[i + 1..] {
+                    pairs.insert((*a.min(b), *a.max(b)));
+                }
+            }
+        }
+        pairs
+            .into_iter()
+            .map(|(a, b)| (self.positions[a].0, self.positions[b].0))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst::prelude::*;
+
+    use super::*;
+
+    fn entities(count: usize) -> Vec<Entity> {
+        let mut world = World::new();
+        (0..count).map(|_| world.create_entity().build()).collect()
+    }
+
+    fn sorted(mut entities: Vec<Entity>) -> Vec<Entity> {
+        entities.sort_by_key(|entity| entity.id());
+        entities
+    }
+
+    // A grid over a 4x4 tile map, like `basic.ron`'s, with entities at the positions.
+    fn grid_with(positions: &[(f32, f32)]) -> (SpatialGrid, Vec<Entity>) {
+        let mut grid = SpatialGrid::new(Vector2::new(-8.0, -8.0), 4.0);
+        let entities = entities(positions.len());
+        for (entity, &(x, z)) in entities.iter().zip(positions) {
+            grid.insert(*entity, Vector2::new(x, z));
+        }
+        (grid, entities)
+    }
+
+    fn hit_entities(hits: &[RayHit]) -> Vec<Entity> {
+        hits.iter().map(|hit| hit.entity).collect()
+    }
+
+    #[test]
+    fn cells_line_up_with_the_map() {
+        let grid = SpatialGrid::new(Vector2::new(-6.0, -8.0), 4.0);
+        assert_eq!(grid.cell(&Vector2::new(-6.0, -8.0)), (0, 0));
+        assert_eq!(grid.cell(&Vector2::new(-2.1, -4.1)), (0, 0));
+        assert_eq!(grid.cell(&Vector2::new(-2.0, -4.0)), (1, 1));
+        assert_eq!(grid.cell(&Vector2::new(-6.5, -8.5)), (-1, -1));
+    }
+
+    #[test]
+    fn within_radius() {
+        let (grid, e) = grid_with(&[
+            (0.0, 0.0),
+            (3.0, 0.0),
+            (4.0, 0.0),
+            (5.0, 0.0),
+            (-3.9, -3.9),
+            (100.0, 100.0),
+        ]);
+        let found = sorted(grid.within_radius(&Vector2::new(0.0, 0.0), 4.0));
+        // Exactly `radius` away is within it.
+        assert_eq!(found, vec![e[0], e[1], e[2]]);
+        // Off the map.
+        assert_eq!(grid.within_radius(&Vector2::new(99.5, 100.0), 1.0), vec![e[5]]);
+        assert!(grid.within_radius(&Vector2::new(50.0, 50.0), 10.0).is_empty());
+    }
+
+    #[test]
+    fn within_rect() {
+        let (grid, e) = grid_with(&[(0.0, 0.0), (3.0, 0.0), (4.0, 4.0), (5.0, 0.0), (-7.0, 2.0)]);
+        let found = sorted(grid.within_rect(&Vector2::new(-1.0, -1.0), &Vector2::new(4.0, 4.0)));
+        // The rectangle's edges are within it.
+        assert_eq!(found, vec![e[0], e[1], e[2]]);
+        let found = grid.within_rect(&Vector2::new(-20.0, 1.0), &Vector2::new(-6.0, 3.0));
+        assert_eq!(found, vec![e[4]]);
+    }
+
+    #[test]
+    fn axis_aligned_raycasts_find_the_nearest_first() {
+        let (grid, e) = grid_with(&[
+            (6.0, 1.0),
+            (-2.0, 0.0),
+            (3.0, 1.2),
+            (-9.0, 0.5),
+            (15.0, 0.5),
+        ]);
+        let hits = grid.raycast(&Vector2::new(-7.0, 0.5), &Vector2::new(1.0, 0.0), 20.0, 0.6);
+        // Not (3, 1.2), too far from the ray, or those behind or beyond it.
+        assert_eq!(hit_entities(&hits), vec![e[1], e[0]]);
+        assert!((hits[0].distance - 5.0).abs() < 1e-4);
+        assert!((hits[1].distance - 13.0).abs() < 1e-4);
+
+        let hits = grid.raycast(&Vector2::new(6.3, 7.0), &Vector2::new(0.0, -2.0), 10.0, 0.5);
+        assert_eq!(hit_entities(&hits), vec![e[0]]);
+        assert!((hits[0].distance - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn raycasts_starting_off_the_map() {
+        let (grid, e) = grid_with(&[(0.0, 0.0), (7.0, 7.0)]);
+        let hits = grid.raycast(&Vector2::new(-20.0, 0.0), &Vector2::new(1.0, 0.0), 30.0, 0.5);
+        assert_eq!(hit_entities(&hits), vec![e[0]]);
+
+        let diagonal = Vector2::new(1.0, 1.0);
+        let hits = grid.raycast(&Vector2::new(-30.0, -30.0), &diagonal, 60.0, 0.1);
+        assert_eq!(hit_entities(&hits), vec![e[0], e[1]]);
+        // Pointing away from the map.
+        assert!(grid.raycast(&Vector2::new(-20.0, 0.0), &-diagonal, 30.0, 5.0).is_empty());
+    }
+
+    #[test]
+    fn raycasts_of_any_length_end_past_the_occupied_cells() {
+        let (grid, e) = grid_with(&[(0.0, 0.0), (7.0, 7.0)]);
+        let diagonal = Vector2::new(1.0, 1.0);
+        let hits = grid.raycast(&Vector2::new(-30.0, -30.0), &diagonal, f32::MAX, 0.1);
+        assert_eq!(hit_entities(&hits), vec![e[0], e[1]]);
+        let far_away = Vector2::new(-1.0e30, 0.0);
+        let hits = grid.raycast(&far_away, &Vector2::new(1.0, 0.0), f32::MAX, 0.5);
+        assert_eq!(hit_entities(&hits), vec![e[0]]);
+        assert!(grid.raycast(&Vector2::new(0.0, 100.0), &diagonal, f32::MAX, 1.0).is_empty());
+        assert!(SpatialGrid::default()
+            .raycast(&Vector2::zeros(), &diagonal, f32::MAX, 1.0)
+            .is_empty());
+    }
+
+    #[test]
+    fn raycasts_reach_neighbouring_cells() {
+        // The ray runs along the cell row z = 0..4 (of the map's cells), near its edge.
+        let (grid, e) = grid_with(&[
+            (0.0, 4.0),
+            (1.0, 8.0),
+            (2.0, 8.25),
+            (3.0, 3.5),
+            (4.0, 7.5),
+        ]);
+        let origin = Vector2::new(-7.0, 3.5);
+        let along_x = Vector2::new(1.0, 0.0);
+        let hits = |radius| hit_entities(&grid.raycast(&origin, &along_x, 20.0, radius));
+
+        // Radius 0 only searches the cells the ray passes through.
+        assert_eq!(hits(0.0), vec![e[3]]);
+        // Exactly `radius` away is found, in the next row.
+        assert_eq!(hits(0.5), vec![e[0], e[3]]);
+        // A whole cell's radius reaches one row either side.
+        assert_eq!(hits(4.0), vec![e[0], e[3], e[4]]);
+        // Any more reaches two rows.
+        assert_eq!(hits(4.5), vec![e[0], e[1], e[3], e[4]]);
+    }
+
+    #[test]
+    fn raycasts_match_checking_every_entity() {
+        // Deterministic, spread over (and past) the map.
+        let positions: Vec<(f32, f32)> = (0..200)
+            .map(|i| {
+                let i = i as f32;
+                ((i * 7.31).sin() * 12.0, (i * 3.17).cos() * 12.0)
+            })
+            .collect();
+        let (grid, e) = grid_with(&positions);
+        for ray in 0..20 {
+            let angle = ray as f32 * 0.7;
+            let origin = Vector2::new(angle.cos() * -10.0, (angle * 1.3).sin() * 10.0);
+            let direction = Vector2::new(angle.sin(), angle.cos());
+            let radius = (ray % 5) as f32 * 0.9;
+
+            let expected: Vec<Entity> = e
+                .iter()
+                .zip(&positions)
+                .filter(|(_, &(x, z))| {
+                    let offset = Vector2::new(x, z) - origin;
+                    let distance = offset.dot(&direction);
+                    (0.0..=15.0).contains(&distance)
+                        && (offset - direction * distance).norm_squared() <= radius * radius
+                })
+                .map(|(entity, _)| *entity)
+                .collect();
+            let hits = grid.raycast(&origin, &direction, 15.0, radius);
+            assert_eq!(sorted(hit_entities(&hits)), sorted(expected), "ray {}", ray);
+            assert!(hits.windows(2).all(|pair| pair[0].distance <= pair[1].distance));
+        }
+    }
+
+    #[test]
+    fn non_finite_positions_are_ignored() {
+        let (mut grid, _) = grid_with(&[(0.0, 0.0)]);
+        let nan = entities(1)[0];
+        grid.insert(nan, Vector2::new(f32::NAN, 0.0));
+        assert_eq!(grid.len(), 1);
+        let hits = grid.raycast(&Vector2::new(-5.0, 0.0), &Vector2::new(1.0, 0.0), 10.0, 1.0);
+        assert_eq!(hits.len(), 1);
+        let nan_direction = Vector2::new(f32::NAN, 1.0);
+        assert!(grid.raycast(&Vector2::zeros(), &nan_direction, 10.0, 1.0).is_empty());
+    }
+
+    // Index an entity with square bounds.
+    fn insert_square(grid: &mut SpatialGrid, entity: Entity, x: f32, z: f32, half: f32) {
+        let centre = Vector2::new(x, z);
+        let extent = Vector2::new(half, half);
+        grid.insert_with_bounds(entity, centre, (centre - extent, centre + extent));
+    }
+
+    #[test]
+    fn candidate_pairs_share_a_cell() {
+        let e = entities(6);
+        let mut grid = SpatialGrid::new(Vector2::zeros(), 4.0);
+        // Spans the four cells around (4, 4).
+        insert_square(&mut grid, e[0], 4.0, 4.0, 1.0);
+        insert_square(&mut grid, e[1], 6.5, 6.5, 0.5);
+        insert_square(&mut grid, e[2], -1.5, -1.5, 0.5);
+        insert_square(&mut grid, e[3], 4.65, 0.65, 0.15);
+        // Shares every cell with e[0], and one each with e[1] and e[3].
+        insert_square(&mut grid, e[4], 4.0, 4.0, 0.5);
+        // Without bounds, it's never a candidate.
+        grid.insert(e[5], Vector2::new(4.0, 4.0));
+
+        assert_eq!(
+            grid.candidate_pairs(),
+            vec![(e[0], e[1]), (e[0], e[3]), (e[0], e[4]), (e[1], e[4]), (e[3], e[4])],
+        );
+    }
+
+    #[test]
+    fn candidate_pairs_across_negative_cells() {
+        let e = entities(3);
+        let mut grid = SpatialGrid::new(Vector2::zeros(), 4.0);
+        insert_square(&mut grid, e[0], 0.0, 0.0, 0.5);
+        insert_square(&mut grid, e[1], -2.5, -2.5, 0.5);
+        insert_square(&mut grid, e[2], 1.5, 1.5, 0.5);
+        assert_eq!(grid.candidate_pairs(), vec![(e[0], e[1]), (e[0], e[2])]);
+
+        grid.clear();
+        assert!(grid.is_empty());
+        assert!(grid.candidate_pairs().is_empty());
+    }
+}
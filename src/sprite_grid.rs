@@ -2,6 +2,8 @@
 //
 // sprite_grid is for creating a tiled grid of 2D sprites.
 
+use std::fs::File;
+use std::path::Path;
 use std::sync::Arc;
 
 use amethyst::{
@@ -37,7 +39,7 @@ use genmesh::{
 use nalgebra::{
     Vector3,
 };
-use ron::de::from_bytes as from_ron_bytes;
+use ron::de::{from_bytes as from_ron_bytes, from_reader as from_ron_reader};
 use serde::{Deserialize, Serialize,};
 
 // Shape generators
@@ -201,13 +203,45 @@ impl From<InternalShape> for (Vec<Position>, Vec<Normal>, Vec<Tangent>, Vec<TexC
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
-struct SerializedSpriteGrid {
+pub struct SerializedSpriteGrid {
     /// Width of the sprite sheet
     pub spritesheet_path: String,
     /// Description of the sprites
     pub grid: Vec<Vec<usize>>,
 }
 
+// Each sprite is drawn as a square of this size.
+pub const TILE_SIZE: f32 = 4.0;
+
+// Half the width (along X) and depth (along Z) of the grid's mesh,
+//  which is centred on the origin.
+pub fn grid_half_extents(num_rows: usize, num_cols: usize) -> (f32, f32) {
+    (
+        TILE_SIZE * num_cols as f32 / 2.0,
+        TILE_SIZE * num_rows as f32 / 2.0,
+    )
+}
+
+// Read a SpriteGrid file, without loading its sprite sheet.
+pub fn read_sprite_grid<P: AsRef<Path>>(path: P) -> Result<SerializedSpriteGrid, Error> {
+    let file = File::open(path.as_ref())
+        .map_err(|_| Error::from_string("error opening SpriteGrid file"))?;
+    from_ron_reader(file).map_err(|_| {
+        Error::from_string(
+            "Failed to parse Ron file for SpriteGrid",
+        )
+    })
+}
+
+// Read the (rows, columns) of a SpriteGrid file,
+//  without loading its sprite sheet.
+pub fn load_grid_dimensions<P: AsRef<Path>>(path: P) -> Result<(usize, usize), Error> {
+    let load_data = read_sprite_grid(path)?;
+    let num_rows = load_data.grid.len();
+    let num_cols = load_data.grid.first().map(|row| row.len()).unwrap_or(0);
+    Ok((num_rows, num_cols))
+}
+
 #[derive(Clone, Debug)]
 pub struct SpriteGridFormat {
     pub texture: Handle<Texture>
@@ -267,8 +301,10 @@ impl Format<MeshData> for SpriteGridFormat {
         };
 
         // smell
+        // The generated plane spans -1..1, so scale it by the half extents.
+        let (half_width, half_depth) = grid_half_extents(num_rows, num_cols);
         let data = sprite_grid.generate::<(Vec<Position>, Vec<Normal>, Vec<Tangent>, Vec<TexCoord>)>(
-            Some((2.0 * num_cols as f32, 2.0 * num_rows as f32, 1.0))
+            Some((half_width, half_depth, 1.0))
         ).into();
 
         Ok(FormatValue::data(data))
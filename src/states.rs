@@ -1,32 +1,170 @@
 use amethyst::{
-    assets::{Completion, ProgressCounter},
-    ecs::{Entities},
-    input::{VirtualKeyCode, is_close_requested, is_key_down},
+    assets::{AssetStorage, Completion, Loader, ProgressCounter},
+    ecs::{Entity, WriteStorage},
+    input::{
+        Bindings, Button, InputHandler, StringBindings, VirtualKeyCode,
+        is_close_requested, is_key_down,
+    },
     prelude::*,
     ui::{
-        UiCreator, UiEvent, UiEventType, UiFinder, UiTransform,
+        Anchor, FontAsset, FontHandle, Interactable, TtfFormat,
+        UiCreator, UiEvent, UiEventType, UiFinder, UiText, UiTransform,
     },
+    utils::application_root_dir,
     renderer::debug_drawing::{DebugLines, DebugLinesParams},
+    window::ScreenDimensions,
+    winit::{dpi::LogicalSize, Window},
 };
 
+use log::{error, info};
+
 use crate::assets::*;
+use crate::key_bindings::{
+    BindingSlot,
+    binding_slots,
+    bound_button,
+    button_from_input_event,
+    rebind,
+    save_user_bindings,
+};
 use crate::level::{
     Level,
     init_level,
+    teardown_level,
 };
+use crate::options::Options;
+use crate::preferences::{Preferences, RESOLUTIONS};
+use crate::utils::delete_hierarchy;
+
+// Whether the gameplay systems should run.
+//
+// Gameplay systems are registered as `pausable(GameplayState::Running)`,
+//  so they're skipped while the `Paused` state is on the stack.
+// Those built from a SystemDesc (which `pausable` can't wrap) check it themselves.
+// The camera following, the UI and the team colours keep running.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameplayState {
+    Running,
+    Paused,
+}
+
+impl Default for GameplayState {
+    fn default() -> Self {
+        GameplayState::Running
+    }
+}
 
 #[derive(Default)]
 pub struct MainMenu {
+    level: Level,
+    ui_root: Option<Entity>,
 }
 
 #[derive(Default)]
 pub struct Loading {
     progress: ProgressCounter,
     level: Level,
+    ui_root: Option<Entity>,
+}
+
+impl MainMenu {
+    pub fn new(level: Level) -> Self {
+        MainMenu {
+            level,
+            ui_root: None,
+        }
+    }
+}
+
+impl Loading {
+    pub fn new(level: Level) -> Self {
+        Loading {
+            progress: ProgressCounter::new(),
+            level,
+            ui_root: None,
+        }
+    }
+}
+
+// Shown when loading a level's assets fails,
+//  listing the assets which failed.
+pub struct LoadingFailed {
+    level: Level,
+    errors: Vec<String>,
+    ui_root: Option<Entity>,
+    labels_dirty: bool,
+}
+
+impl LoadingFailed {
+    pub fn new(level: Level, errors: Vec<String>) -> Self {
+        LoadingFailed {
+            level,
+            errors,
+            ui_root: None,
+            labels_dirty: true,
+        }
+    }
 }
 
+// Everything Main creates is deleted when it stops,
+//  so it's safe to switch away from it (e.g. to restart the level).
 pub struct Main {
     level: Level,
+    ui_root: Option<Entity>,
+    debug_overlay_root: Option<Entity>,
+}
+
+// Pushed on top of `Main`.
+pub struct Paused {
+    level: Level,
+    ui_root: Option<Entity>,
+}
+
+// Pushed on top of `MainMenu` or `Paused`.
+//
+// Changes are applied as they're made,
+//  and saved to the user's preferences when leaving the state.
+#[derive(Default)]
+pub struct Settings {
+    preferences: Preferences,
+    ui_root: Option<Entity>,
+    labels_dirty: bool,
+}
+
+// Pushed on top of `Settings`.
+//
+// Lists a row for each binding; clicking a row captures the next
+//  key, mouse or controller button pressed and assigns it.
+#[derive(Default)]
+pub struct KeyBindings {
+    bindings: Bindings<StringBindings>,
+    slots: Vec<BindingSlot>,
+    // The (label, value) entities for each slot's row
+    rows: Vec<(Entity, Entity)>,
+    ui_root: Option<Entity>,
+    capturing: Option<usize>,
+    status: String,
+    changed: bool,
+    status_dirty: bool,
+}
+
+impl Main {
+    pub fn new(level: Level) -> Self {
+        Main {
+            level,
+            ui_root: None,
+            debug_overlay_root: None,
+        }
+    }
+}
+
+impl Paused {
+    pub fn new(level: Level) -> Self {
+        Paused {
+            level,
+            ui_root: None,
+        }
+    }
 }
 
 fn ui_transform_id_of_ui_event<'a, 'b>(
@@ -44,30 +182,35 @@ fn ui_transform_id_of_ui_event<'a, 'b>(
     }
 }
 
-fn unload_main_menu(
-    world: &mut World,
-) -> () {
-    let main_menu_ui_ids = [
-        "main_menu_background",
-        "main_menu_game_title",
-        "start_game_button",
-        "exit_game_button"
-    ];
-    world.exec(|(finder, entities): (UiFinder<'_>, Entities<'_>)| {
-        for main_menu_ui_id in main_menu_ui_ids.iter() {
-           if let Some(entity) = finder.find(main_menu_ui_id) {
-               entities.delete(entity);
-           }
-        }
-    });
+// Create a UI from a prefab, returning its root entity.
+fn create_ui(world: &mut World, path: &str) -> Entity {
+    let mut progress: ProgressCounter = ProgressCounter::new();
+    world.exec(|mut creator: UiCreator<'_>| creator.create(path, &mut progress))
 }
 
 impl SimpleState for MainMenu {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
-        let mut progress: ProgressCounter = ProgressCounter::new();
-        data.world.exec(|mut creator: UiCreator<'_>| {
-            creator.create("ui/main_menu.ron", &mut progress);
-        });
+        // Nothing is needed from the level which was being played (if any).
+        unload_level_assets(data.world);
+
+        self.ui_root = Some(create_ui(data.world, "ui/main_menu.ron"));
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(ui_root) = self.ui_root.take() {
+            delete_hierarchy(data.world, ui_root);
+        }
+    }
+
+    // The menu is hidden under `Settings`, so its buttons can't be clicked through it.
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(ui_root) = self.ui_root.take() {
+            delete_hierarchy(data.world, ui_root);
+        }
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.ui_root = Some(create_ui(data.world, "ui/main_menu.ron"));
     }
 
     fn handle_event(
@@ -96,8 +239,11 @@ impl SimpleState for MainMenu {
                             if id == "exit_game_button" {
                                 return Trans::Quit
                             }
+                            if id == "settings_button" {
+                                return Trans::Push(Box::new(Settings::default()));
+                            }
                             if id == "start_game_button" {
-                                let next_state = Loading::default();
+                                let next_state = Loading::new(self.level.clone());
 
                                 return Trans::Switch(Box::new(next_state));
                             }
@@ -116,49 +262,188 @@ impl SimpleState for MainMenu {
 
 impl SimpleState for Loading {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
-        data.world.exec(|mut creator: UiCreator<'_>| {
-            creator.create("ui/loading.ron", &mut self.progress);
-            creator.create("ui/fps.ron", &mut self.progress);
-        });
+        let progress = &mut self.progress;
+        self.ui_root = Some(data.world.exec(|mut creator: UiCreator<'_>| {
+            creator.create("ui/loading.ron", progress)
+        }));
+
+        load_level_assets(data.world, &self.level, &mut self.progress);
+    }
 
-        load_assets(data.world, &mut self.progress);
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(ui_root) = self.ui_root.take() {
+            delete_hierarchy(data.world, ui_root);
+        }
     }
 
     fn update(
         &mut self,
         data: &mut StateData<'_, GameData<'_, '_>>
     ) -> SimpleTrans {
-        unload_main_menu(&mut data.world);
+        self.refresh_progress(&mut data.world);
 
         match self.progress.complete() {
             Completion::Failed => {
-                println!("Failed loading assets: {:?}", self.progress.errors());
-                Trans::Quit
+                let errors: Vec<String> = self.progress
+                    .errors()
+                    .iter()
+                    .map(|error| format!("{}: {}", error.asset_name, error.error))
+                    .collect();
+                for error in &errors {
+                    error!("Failed loading asset {}", error);
+                }
+
+                // Release everything, so that retrying loads the assets afresh.
+                unload_level_assets(data.world);
+
+                let next_state = LoadingFailed::new(self.level.clone(), errors);
+                Trans::Switch(Box::new(next_state))
             }
             Completion::Complete => {
-                println!("Assets loaded ({}/{}), swapping state",
-                         self.progress.num_finished(),
-                         self.progress.num_assets());
-                if let Some(entity) = data
-                    .world
-                    .exec(|finder: UiFinder<'_>| finder.find("loading"))
-                {
-                    let _ = data.world.delete_entity(entity);
-                }
-                Trans::Switch(Box::new(Main {
-                    level: self.level.clone(),
-                }))
+                info!("Assets loaded ({}/{}), swapping state",
+                      self.progress.num_finished(),
+                      self.progress.num_assets());
+                Trans::Switch(Box::new(Main::new(self.level.clone())))
             }
             Completion::Loading => {
-                println!("Loading.. ({}/{})",
-                         self.progress.num_finished(),
-                         self.progress.num_assets());
                 Trans::None
             }
         }
     }
 }
 
+impl Loading {
+    // Show the loading progress in the progress bar and its labels.
+    //
+    // Only the assets this level needs which weren't already loaded are counted.
+    fn refresh_progress(&self, world: &mut World) -> () {
+        let num_assets = self.progress.num_assets();
+        let num_finished = self.progress.num_finished();
+        let fraction = if num_assets == 0 {
+            1.0
+        } else {
+            num_finished as f32 / num_assets as f32
+        };
+
+        let title = format!("Loading {}", self.level.name);
+        let percentage = format!("{:.0}% ({}/{})", fraction * 100.0, num_finished, num_assets);
+
+        world.exec(
+            |(finder, mut ui_texts, mut ui_transforms): (
+                UiFinder<'_>,
+                WriteStorage<'_, UiText>,
+                WriteStorage<'_, UiTransform>,
+            )| {
+                for (id, value) in [("loading", title), ("loading_percentage", percentage)].iter() {
+                    let ui_text = finder
+                        .find(id)
+                        .and_then(|entity| ui_texts.get_mut(entity));
+                    if let Some(ui_text) = ui_text {
+                        ui_text.text = value.clone();
+                    }
+                }
+
+                let bar_width = finder
+                    .find("loading_bar")
+                    .and_then(|entity| ui_transforms.get(entity))
+                    .map(|ui_transform| ui_transform.width);
+                let fill = finder
+                    .find("loading_bar_fill")
+                    .and_then(|entity| ui_transforms.get_mut(entity));
+                if let (Some(bar_width), Some(fill)) = (bar_width, fill) {
+                    fill.width = bar_width * fraction;
+                }
+            },
+        );
+    }
+}
+
+impl SimpleState for LoadingFailed {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.labels_dirty = true;
+
+        let mut progress: ProgressCounter = ProgressCounter::new();
+        self.ui_root = Some(data.world.exec(|mut creator: UiCreator<'_>| {
+            creator.create("ui/loading_error.ron", &mut progress)
+        }));
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(ui_root) = self.ui_root.take() {
+            delete_hierarchy(data.world, ui_root);
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        match &event {
+            StateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    Trans::Quit
+                } else {
+                    Trans::None
+                }
+            }
+            StateEvent::Ui(ui_event) => {
+                match &ui_event.event_type {
+                    UiEventType::Click => {
+                        let maybe_id = ui_transform_id_of_ui_event(
+                            &ui_event,
+                            &data.world
+                        );
+                        if let Some(id) = maybe_id {
+                            if id == "retry_button" {
+                                let next_state = Loading::new(self.level.clone());
+
+                                return Trans::Switch(Box::new(next_state));
+                            }
+                            if id == "back_to_menu_button" {
+                                let next_state = MainMenu::new(self.level.clone());
+
+                                return Trans::Switch(Box::new(next_state));
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                Trans::None
+            }
+            _ => {
+                Trans::None
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: &mut StateData<'_, GameData<'_, '_>>
+    ) -> SimpleTrans {
+        if self.labels_dirty {
+            // The UI is loaded asynchronously, so retry until the label's found.
+            let errors = self.errors.join("\n");
+            let found = data.world.exec(
+                |(finder, mut ui_texts): (UiFinder<'_>, WriteStorage<'_, UiText>)| {
+                    let ui_text = finder
+                        .find("loading_error_list")
+                        .and_then(|entity| ui_texts.get_mut(entity));
+                    match ui_text {
+                        Some(ui_text) => {
+                            ui_text.text = errors;
+                            true
+                        }
+                        None => false,
+                    }
+                }
+            );
+            self.labels_dirty = !found;
+        }
+        Trans::None
+    }
+}
+
 impl SimpleState for Main {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         // Setup debug lines as a resource
@@ -168,13 +453,31 @@ impl SimpleState for Main {
         world.insert(DebugLines::new());
         world.insert(DebugLinesParams { line_width: 5.0 });
 
-        let maybe_assets = world.try_fetch::<Assets>().map(|a| (*a).clone());
+        let maybe_registry = world.try_fetch::<AssetRegistry>().map(|r| (*r).clone());
 
-        if let Some(assets) = maybe_assets {
-            init_level(world, assets, &self.level);
+        if let Some(registry) = maybe_registry {
+            init_level(world, registry, &self.level);
         } else {
-            println!("Couldn't read assets");
+            error!("Couldn't read assets");
         }
+
+        let mut progress: ProgressCounter = ProgressCounter::new();
+        self.ui_root = Some(world.exec(|mut creator: UiCreator<'_>| {
+            creator.create("ui/fps.ron", &mut progress)
+        }));
+        // Empty until the DebugSystem shows it.
+        self.debug_overlay_root = Some(world.exec(|mut creator: UiCreator<'_>| {
+            creator.create("ui/debug_overlay.ron", &mut progress)
+        }));
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let ui_roots: Vec<Entity> = self.ui_root
+            .take()
+            .into_iter()
+            .chain(self.debug_overlay_root.take())
+            .collect();
+        teardown_level(data.world, &ui_roots);
     }
 
     fn handle_event(
@@ -184,11 +487,517 @@ impl SimpleState for Main {
     ) -> SimpleTrans {
         if let StateEvent::Window(event) = &event {
             // Handle Quitting
-            let window_closed = is_close_requested(&event);
-            let esc_key_down = is_key_down(&event, VirtualKeyCode::Escape);
-            if window_closed || esc_key_down {
+            if is_close_requested(&event) {
                 return Trans::Quit;
             }
+            if is_key_down(&event, VirtualKeyCode::Escape) {
+                return Trans::Push(Box::new(Paused::new(self.level.clone())));
+            }
+        }
+        Trans::None
+    }
+}
+
+impl SimpleState for Paused {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
+
+        *world.write_resource::<GameplayState>() = GameplayState::Paused;
+
+        self.ui_root = Some(create_ui(world, "ui/pause_menu.ron"));
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
+
+        if let Some(ui_root) = self.ui_root.take() {
+            delete_hierarchy(world, ui_root);
+        }
+
+        *world.write_resource::<GameplayState>() = GameplayState::Running;
+    }
+
+    // The menu is hidden under `Settings`; the game stays paused.
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(ui_root) = self.ui_root.take() {
+            delete_hierarchy(data.world, ui_root);
+        }
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.ui_root = Some(create_ui(data.world, "ui/pause_menu.ron"));
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        match &event {
+            StateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    Trans::Quit
+                } else if is_key_down(&event, VirtualKeyCode::Escape) {
+                    Trans::Pop
+                } else {
+                    Trans::None
+                }
+            }
+            StateEvent::Ui(ui_event) => {
+                match &ui_event.event_type {
+                    UiEventType::Click => {
+                        let maybe_id = ui_transform_id_of_ui_event(
+                            &ui_event,
+                            &data.world
+                        );
+                        if let Some(id) = maybe_id {
+                            if id == "resume_button" {
+                                return Trans::Pop
+                            }
+                            if id == "settings_button" {
+                                return Trans::Push(Box::new(Settings::default()));
+                            }
+                            if id == "restart_level_button" {
+                                let next_state = Main::new(self.level.clone());
+
+                                return Trans::Sequence(vec![
+                                    Trans::Pop,
+                                    Trans::Switch(Box::new(next_state)),
+                                ]);
+                            }
+                            if id == "quit_to_menu_button" {
+                                let next_state = MainMenu::new(self.level.clone());
+
+                                return Trans::Sequence(vec![
+                                    Trans::Pop,
+                                    Trans::Switch(Box::new(next_state)),
+                                ]);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                Trans::None
+            }
+            _ => {
+                Trans::None
+            }
+        }
+    }
+}
+
+impl Settings {
+    fn current_dimensions(&self, world: &World) -> (u32, u32) {
+        self.preferences.dimensions.unwrap_or_else(|| {
+            let screen_dimensions = world.read_resource::<ScreenDimensions>();
+            (screen_dimensions.width() as u32, screen_dimensions.height() as u32)
+        })
+    }
+
+    fn cycle_resolution(&mut self, world: &World, step: isize) -> () {
+        let num_resolutions = RESOLUTIONS.len() as isize;
+        let current = self.current_dimensions(world);
+        let index = RESOLUTIONS
+            .iter()
+            .position(|&resolution| resolution == current)
+            .map(|index| index as isize + step)
+            .unwrap_or(0);
+        let index = ((index % num_resolutions) + num_resolutions) % num_resolutions;
+
+        let (width, height) = RESOLUTIONS[index as usize];
+        self.preferences.dimensions = Some((width, height));
+
+        let window = world.read_resource::<Window>();
+        window.set_inner_size(LogicalSize::new(width as f64, height as f64));
+    }
+
+    fn toggle_fullscreen(&mut self, world: &World) -> () {
+        self.preferences.fullscreen = !self.preferences.fullscreen;
+
+        let window = world.read_resource::<Window>();
+        if self.preferences.fullscreen {
+            window.set_fullscreen(Some(window.get_current_monitor()));
+        } else {
+            window.set_fullscreen(None);
+        }
+    }
+
+    // The UI is loaded asynchronously,
+    //  so the labels are retried each frame until they've all been found.
+    fn refresh_labels(&mut self, world: &mut World) -> () {
+        let (width, height) = self.current_dimensions(world);
+        let values = [
+            ("resolution_value", format!("{}x{}", width, height)),
+            ("fullscreen_value", String::from(
+                if self.preferences.fullscreen { "On" } else { "Off" }
+            )),
+            ("control_scheme_value", String::from(
+                self.preferences.control_scheme.label()
+            )),
+        ];
+
+        let all_found = world.exec(
+            |(finder, mut ui_texts): (UiFinder<'_>, WriteStorage<'_, UiText>)| {
+                let mut all_found = true;
+                for (id, value) in values.iter() {
+                    let ui_text = finder
+                        .find(id)
+                        .and_then(|entity| ui_texts.get_mut(entity));
+                    match ui_text {
+                        Some(ui_text) => ui_text.text = value.clone(),
+                        None => all_found = false,
+                    }
+                }
+                all_found
+            }
+        );
+
+        self.labels_dirty = !all_found;
+    }
+}
+
+impl SimpleState for Settings {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
+
+        self.preferences = (*world.read_resource::<Preferences>()).clone();
+        self.labels_dirty = true;
+
+        self.ui_root = Some(create_ui(world, "ui/settings.ron"));
+    }
+
+    // Hidden under `KeyBindings`.
+    fn on_pause(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        if let Some(ui_root) = self.ui_root.take() {
+            delete_hierarchy(data.world, ui_root);
+        }
+    }
+
+    fn on_resume(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        self.labels_dirty = true;
+        self.ui_root = Some(create_ui(data.world, "ui/settings.ron"));
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
+
+        if let Some(ui_root) = self.ui_root.take() {
+            delete_hierarchy(world, ui_root);
+        }
+
+        *world.write_resource::<Preferences>() = self.preferences.clone();
+        if let Err(err) = self.preferences.save() {
+            error!("Failed saving preferences: {}", err);
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        match &event {
+            StateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    Trans::Quit
+                } else if is_key_down(&event, VirtualKeyCode::Escape) {
+                    Trans::Pop
+                } else {
+                    Trans::None
+                }
+            }
+            StateEvent::Ui(ui_event) => {
+                match &ui_event.event_type {
+                    UiEventType::Click => {
+                        let maybe_id = ui_transform_id_of_ui_event(
+                            &ui_event,
+                            &data.world
+                        );
+                        if let Some(id) = maybe_id {
+                            match id.as_str() {
+                                "resolution_prev_button" => {
+                                    self.cycle_resolution(&data.world, -1);
+                                }
+                                "resolution_next_button" => {
+                                    self.cycle_resolution(&data.world, 1);
+                                }
+                                "fullscreen_toggle_button" => {
+                                    self.toggle_fullscreen(&data.world);
+                                }
+                                "control_scheme_toggle_button" => {
+                                    self.preferences.control_scheme =
+                                        self.preferences.control_scheme.next();
+                                    // Gameplay systems read the scheme from the resource.
+                                    *data.world.write_resource::<Preferences>() =
+                                        self.preferences.clone();
+                                }
+                                "key_bindings_button" => {
+                                    return Trans::Push(Box::new(KeyBindings::default()));
+                                }
+                                "settings_back_button" => {
+                                    return Trans::Pop;
+                                }
+                                _ => {}
+                            }
+                            self.labels_dirty = true;
+                        }
+                    }
+                    _ => {}
+                }
+                Trans::None
+            }
+            _ => {
+                Trans::None
+            }
+        }
+    }
+
+    fn update(
+        &mut self,
+        data: &mut StateData<'_, GameData<'_, '_>>
+    ) -> SimpleTrans {
+        if self.labels_dirty {
+            self.refresh_labels(&mut data.world);
+        }
+        Trans::None
+    }
+}
+
+static BINDING_ROW_ID_PREFIX: &str = "binding_row_";
+
+impl KeyBindings {
+    fn create_rows(&mut self, world: &mut World) -> () {
+        let font: FontHandle = world.read_resource::<Loader>().load(
+            "font/square.ttf",
+            TtfFormat,
+            (),
+            &world.read_resource::<AssetStorage<FontAsset>>(),
+        );
+
+        for (index, slot) in self.slots.iter().enumerate() {
+            let y = -120.0 - 40.0 * index as f32;
+            let label = world
+                .create_entity()
+                .with(UiTransform::new(
+                    format!("binding_label_{}", index),
+                    Anchor::TopMiddle,
+                    Anchor::Middle,
+                    -120.0, y, 1.0,
+                    240.0, 32.0,
+                ))
+                .with(UiText::new(
+                    font.clone(),
+                    slot.label(),
+                    [0.3, 0.3, 0.3, 1.0],
+                    20.0,
+                ))
+                .build();
+            let value = world
+                .create_entity()
+                .with(UiTransform::new(
+                    format!("{}{}", BINDING_ROW_ID_PREFIX, index),
+                    Anchor::TopMiddle,
+                    Anchor::Middle,
+                    120.0, y, 1.0,
+                    240.0, 32.0,
+                ))
+                .with(UiText::new(
+                    font.clone(),
+                    String::new(),
+                    [0.0, 0.0, 0.0, 1.0],
+                    20.0,
+                ))
+                .with(Interactable)
+                .build();
+            self.rows.push((label, value));
+        }
+
+        self.refresh_rows(world);
+    }
+
+    fn delete_rows(&mut self, world: &mut World) -> () {
+        for (label, value) in self.rows.drain(..) {
+            let _ = world.delete_entity(label);
+            let _ = world.delete_entity(value);
+        }
+    }
+
+    fn refresh_rows(&self, world: &mut World) -> () {
+        let mut ui_texts = world.write_storage::<UiText>();
+        for (index, (slot, (_, value))) in self.slots.iter().zip(&self.rows).enumerate() {
+            let text = if self.capturing == Some(index) {
+                String::from("Press a button...")
+            } else {
+                bound_button(&self.bindings, slot)
+                    .map(|button| format!("{:?}", button))
+                    .unwrap_or_else(|| String::from("(unbound)"))
+            };
+            if let Some(ui_text) = ui_texts.get_mut(*value) {
+                ui_text.text = text;
+            }
+        }
+    }
+
+    fn set_status(&mut self, status: String) -> () {
+        self.status = status;
+        self.status_dirty = true;
+    }
+
+    // The status label is loaded asynchronously,
+    //  so it's retried each frame until it's been found.
+    fn refresh_status(&mut self, world: &mut World) -> () {
+        let status = &self.status;
+        let found = world.exec(
+            |(finder, mut ui_texts): (UiFinder<'_>, WriteStorage<'_, UiText>)| {
+                let ui_text = finder
+                    .find("key_bindings_status")
+                    .and_then(|entity| ui_texts.get_mut(entity));
+                if let Some(ui_text) = ui_text {
+                    ui_text.text = status.clone();
+                    true
+                } else {
+                    false
+                }
+            }
+        );
+        self.status_dirty = !found;
+    }
+
+    fn apply_bindings(&mut self, world: &mut World) -> () {
+        world
+            .write_resource::<InputHandler<StringBindings>>()
+            .bindings = self.bindings.clone();
+        self.changed = true;
+        self.refresh_rows(world);
+    }
+
+    fn reset_to_defaults(&mut self, world: &mut World) -> () {
+        let defaults = application_root_dir()
+            .map(|app_root| world.read_resource::<Options>().default_input_config_path(&app_root))
+            .map_err(|err| format!("{}", err))
+            .and_then(|path| {
+                Bindings::<StringBindings>::load(path).map_err(|err| format!("{}", err))
+            });
+        match defaults {
+            Ok(bindings) => {
+                self.bindings = bindings;
+                self.capturing = None;
+                self.set_status(String::from("Reset to the default bindings"));
+                self.apply_bindings(world);
+            }
+            Err(err) => {
+                self.set_status(format!("Failed loading the default bindings: {}", err));
+            }
+        }
+    }
+}
+
+impl SimpleState for KeyBindings {
+    fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
+
+        self.bindings = world
+            .read_resource::<InputHandler<StringBindings>>()
+            .bindings
+            .clone();
+        self.slots = binding_slots(&self.bindings);
+
+        let mut progress: ProgressCounter = ProgressCounter::new();
+        self.ui_root = Some(world.exec(|mut creator: UiCreator<'_>| {
+            creator.create("ui/key_bindings.ron", &mut progress)
+        }));
+
+        self.create_rows(world);
+        self.set_status(String::from("Click a binding to change it"));
+    }
+
+    fn on_stop(&mut self, data: StateData<'_, GameData<'_, '_>>) {
+        let StateData { world, .. } = data;
+
+        self.delete_rows(world);
+        if let Some(ui_root) = self.ui_root.take() {
+            delete_hierarchy(world, ui_root);
+        }
+
+        if self.changed {
+            if let Err(err) = save_user_bindings(&self.bindings) {
+                error!("Failed saving input bindings: {}", err);
+            }
+        }
+    }
+
+    fn handle_event(
+        &mut self,
+        data: StateData<'_, GameData<'_, '_>>,
+        event: StateEvent,
+    ) -> SimpleTrans {
+        let StateData { world, .. } = data;
+
+        match &event {
+            StateEvent::Window(event) => {
+                if is_close_requested(&event) {
+                    return Trans::Quit;
+                }
+                if is_key_down(&event, VirtualKeyCode::Escape) {
+                    if self.capturing.take().is_some() {
+                        self.set_status(String::from("Cancelled"));
+                        self.refresh_rows(world);
+                    } else {
+                        return Trans::Pop;
+                    }
+                }
+            }
+            StateEvent::Ui(ui_event) => {
+                if let UiEventType::Click = &ui_event.event_type {
+                    let maybe_id = ui_transform_id_of_ui_event(&ui_event, world);
+                    if let Some(id) = maybe_id {
+                        if id == "key_bindings_back_button" {
+                            return Trans::Pop;
+                        }
+                        if id == "key_bindings_reset_button" {
+                            self.reset_to_defaults(world);
+                        } else if id.starts_with(BINDING_ROW_ID_PREFIX) {
+                            let index = id[BINDING_ROW_ID_PREFIX.len()..].parse().ok();
+                            if let Some(index) = index {
+                                self.capturing = Some(index);
+                                self.set_status(format!(
+                                    "Press a button for {}, or Escape to cancel",
+                                    self.slots[index].label()
+                                ));
+                                self.refresh_rows(world);
+                            }
+                        }
+                    }
+                }
+            }
+            StateEvent::Input(input_event) => {
+                let button = button_from_input_event(input_event)
+                    .filter(|button| *button != Button::Key(VirtualKeyCode::Escape));
+                if let (Some(index), Some(button)) = (self.capturing, button) {
+                    self.capturing = None;
+                    let slot = self.slots[index].clone();
+                    match rebind(&mut self.bindings, &slot, button) {
+                        Ok(()) => {
+                            self.set_status(format!("Rebound {}", slot.label()));
+                            self.apply_bindings(world);
+                        }
+                        Err(err) => {
+                            self.set_status(format!("Can't rebind {}: {}", slot.label(), err));
+                            self.refresh_rows(world);
+                        }
+                    }
+                }
+            }
+        }
+        Trans::None
+    }
+
+    fn update(
+        &mut self,
+        data: &mut StateData<'_, GameData<'_, '_>>
+    ) -> SimpleTrans {
+        if self.status_dirty {
+            self.refresh_status(&mut data.world);
         }
         Trans::None
     }
@@ -0,0 +1,151 @@
+use amethyst;
+use amethyst::{
+    animation::{
+        get_animation_set,
+        Animation,
+        AnimationCommand,
+        AnimationControlSet,
+        AnimationSet,
+        ControlState,
+        EndControl,
+    },
+    assets::AssetStorage,
+    core::transform::Transform,
+    derive::SystemDesc,
+    ecs::prelude::{
+        Entities, Join, Read, ReadStorage, System, SystemData, WriteStorage,
+    },
+};
+use log::warn;
+
+use crate::animation::{AnimationController, AnimationRequest};
+use crate::states::GameplayState;
+
+// Applies AnimationControllers' requests to the AnimationControlSets
+//  which the AnimationBundle's systems play.
+//
+// While the game is paused, the playing clips are paused too,
+//  and requests wait until it resumes.
+#[derive(Default, SystemDesc)]
+pub struct AnimationControllerSystem {
+    paused: bool,
+}
+
+impl<'a> System<'a> for AnimationControllerSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, AnimationController>,
+        ReadStorage<'a, AnimationSet<usize, Transform>>,
+        WriteStorage<'a, AnimationControlSet<usize, Transform>>,
+        Read<'a, AssetStorage<Animation<Transform>>>,
+        Read<'a, GameplayState>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            mut controllers,
+            animation_sets,
+            mut control_sets,
+            animation_storage,
+            gameplay_state,
+        ) = data;
+
+        let paused = *gameplay_state != GameplayState::Running;
+        if paused != self.paused {
+            self.paused = paused;
+            for control_set in (&mut control_sets).join() {
+                let ids: Vec<usize> = control_set.animations
+                    .iter()
+                    .filter(|(_, control)| match control.state {
+                        ControlState::Running(_) => paused,
+                        ControlState::Paused(_) => !paused,
+                        _ => false,
+                    })
+                    .map(|(id, _)| *id)
+                    .collect();
+                for id in ids {
+                    if paused {
+                        control_set.pause(id);
+                    } else {
+                        control_set.start(id);
+                    }
+                }
+            }
+        }
+        if paused {
+            return;
+        }
+
+        // Entities without an AnimationSet yet (i.e. whose model hasn't loaded)
+        //  keep their requests until they have one.
+        for (entity, controller, animation_set) in
+            (&entities, &mut controllers, &animation_sets).join()
+        {
+            if controller.pending.is_empty() {
+                continue;
+            }
+            let control_set = match get_animation_set(&mut control_sets, entity) {
+                Some(control_set) => control_set,
+                None => continue,
+            };
+
+            for request in controller.pending.drain(..) {
+                let clip = match &request {
+                    AnimationRequest::Play { clip, .. }
+                    | AnimationRequest::Stop { clip }
+                    | AnimationRequest::Blend { clip, .. } => clip,
+                };
+                let index = match controller.clips.get(clip) {
+                    Some(index) => *index,
+                    None => {
+                        warn!("{:?} has no animation clip '{}'", entity, clip);
+                        continue;
+                    }
+                };
+                let animation = match animation_set.get(&index) {
+                    Some(animation) => animation,
+                    None => continue,
+                };
+
+                match request {
+                    AnimationRequest::Play { looping, rate, .. } => {
+                        if control_set.has_animation(index) {
+                            control_set.set_input(index, 0.0);
+                            control_set.set_rate(index, rate);
+                        } else {
+                            let end = if looping {
+                                EndControl::Loop(None)
+                            } else {
+                                EndControl::Normal
+                            };
+                            control_set.add_animation(
+                                index,
+                                animation,
+                                end,
+                                rate,
+                                AnimationCommand::Start,
+                            );
+                        }
+                    }
+                    AnimationRequest::Stop { .. } => {
+                        control_set.abort(index);
+                    }
+                    AnimationRequest::Blend { weight, .. } => {
+                        // Weight each of the nodes (and channels) the clip animates.
+                        let weights = animation_storage
+                            .get(animation)
+                            .map(|animation| {
+                                animation.nodes
+                                    .iter()
+                                    .map(|(node, channel, _)| (*node, channel.clone(), weight))
+                                    .collect()
+                            })
+                            .unwrap_or_else(Vec::new);
+                        control_set.set_blend_weight(index, weights);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use amethyst;
+use amethyst::{
+    derive::SystemDesc,
+    ecs::prelude::{Join, ReadExpect, System, SystemData, WriteStorage},
+    renderer::Camera,
+    window::ScreenDimensions,
+};
+
+// Keeps perspective cameras' aspect ratio in step with the window,
+//  e.g. after it's resized.
+//
+// Only reads the ScreenDimensions resource,
+//  so it can be driven by changing that resource directly.
+#[derive(Default, SystemDesc)]
+pub struct CameraAspectSystem;
+
+impl<'a> System<'a> for CameraAspectSystem {
+    type SystemData = (
+        ReadExpect<'a, ScreenDimensions>,
+        WriteStorage<'a, Camera>,
+    );
+
+    fn run(&mut self, (screen_dimensions, mut cameras): Self::SystemData) {
+        if screen_dimensions.width() <= 0.0 || screen_dimensions.height() <= 0.0 {
+            // e.g. while the window is minimized
+            return;
+        }
+        let aspect_ratio = screen_dimensions.aspect_ratio();
+
+        // Also covers cameras created since the last resize (e.g. on restarting the level).
+        for camera in (&mut cameras).join() {
+            if let Some(perspective) = camera.projection_mut().as_perspective_mut() {
+                if (perspective.aspect() - aspect_ratio).abs() > std::f32::EPSILON {
+                    perspective.set_aspect(aspect_ratio);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst::ecs::{Builder, Entity, RunNow, World, WorldExt};
+
+    use super::*;
+    use crate::camera::{perspective_projection, CAMERA_FOV, DEFAULT_ASPECT_RATIO};
+
+    fn window(width: u32, height: u32) -> World {
+        let mut world = World::new();
+        world.register::<Camera>();
+        world.insert(ScreenDimensions::new(width, height, 1.0));
+        world
+    }
+
+    fn add_camera(world: &mut World) -> Entity {
+        world
+            .create_entity()
+            .with(Camera::from(perspective_projection(DEFAULT_ASPECT_RATIO)))
+            .build()
+    }
+
+    // The camera's (aspect ratio, vertical field of view).
+    fn perspective(world: &World, camera: Entity) -> (f32, f32) {
+        let cameras = world.read_storage::<Camera>();
+        let perspective = cameras
+            .get(camera)
+            .and_then(|camera| camera.projection().as_perspective())
+            .expect("camera has a perspective projection");
+        (perspective.aspect(), perspective.fovy())
+    }
+
+    fn assert_close(actual: f32, expected: f32) -> () {
+        assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn the_aspect_ratio_follows_the_window() {
+        let mut world = window(800, 600);
+        let camera = add_camera(&mut world);
+
+        world.write_resource::<ScreenDimensions>().update(1920.0, 1080.0);
+        CameraAspectSystem.run_now(&world);
+        let (aspect, fov) = perspective(&world, camera);
+        assert_close(aspect, 16.0 / 9.0);
+        assert_close(fov, CAMERA_FOV);
+
+        world.write_resource::<ScreenDimensions>().update(600.0, 800.0);
+        CameraAspectSystem.run_now(&world);
+        let (aspect, fov) = perspective(&world, camera);
+        assert_close(aspect, 0.75);
+        assert_close(fov, CAMERA_FOV);
+    }
+
+    #[test]
+    fn cameras_created_after_a_resize_are_updated() {
+        let mut world = window(1280, 720);
+        CameraAspectSystem.run_now(&world);
+        let camera = add_camera(&mut world);
+
+        CameraAspectSystem.run_now(&world);
+        assert_close(perspective(&world, camera).0, 16.0 / 9.0);
+    }
+
+    #[test]
+    fn a_minimized_window_keeps_the_aspect_ratio() {
+        let mut world = window(1280, 720);
+        let camera = add_camera(&mut world);
+        CameraAspectSystem.run_now(&world);
+
+        world.write_resource::<ScreenDimensions>().update(1280.0, 0.0);
+        CameraAspectSystem.run_now(&world);
+        assert_close(perspective(&world, camera).0, 16.0 / 9.0);
+    }
+}
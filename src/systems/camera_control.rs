@@ -0,0 +1,133 @@
+use amethyst;
+use amethyst::{
+    core::timing::Time,
+    derive::SystemDesc,
+    ecs::prelude::{
+        Join, Read, ReadExpect, System, SystemData, WriteStorage,
+    },
+    input::{InputHandler, StringBindings},
+    window::ScreenDimensions,
+};
+use nalgebra::Vector3;
+
+use crate::camera::{CameraController, CameraFollow, CameraMode};
+
+// Radians per pixel dragged, when orbiting
+const DRAG_ORBIT_SPEED: f32 = 0.01;
+// Units per pixel dragged (per unit of camera distance), when panning
+const DRAG_PAN_SPEED: f32 = 0.002;
+
+#[derive(Default, SystemDesc)]
+pub struct CameraControlSystem {
+    mode_was_down: bool,
+    snap_was_down: bool,
+    last_mouse_position: Option<(f32, f32)>,
+}
+
+impl<'a> System<'a> for CameraControlSystem {
+    type SystemData = (
+        WriteStorage<'a, CameraController>,
+        WriteStorage<'a, CameraFollow>,
+        Read<'a, InputHandler<StringBindings>>,
+        ReadExpect<'a, ScreenDimensions>,
+        Read<'a, Time>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            mut controllers,
+            mut follows,
+            input,
+            screen_dimensions,
+            time,
+        ) = data;
+
+        let mode_down = input.action_is_down("camera_mode").unwrap_or(false);
+        let mode_pressed = mode_down && !self.mode_was_down;
+        self.mode_was_down = mode_down;
+
+        let snap_down = input.action_is_down("camera_snap").unwrap_or(false);
+        let snap_pressed = snap_down && !self.snap_was_down;
+        self.snap_was_down = snap_down;
+
+        let zoom = input.axis_value("camera_zoom").unwrap_or(0.0) as f32;
+        let orbit = input.axis_value("camera_orbit").unwrap_or(0.0) as f32;
+        let dragging = input.action_is_down("camera_drag").unwrap_or(false);
+
+        let mouse_position = input.mouse_position();
+        let (drag_x, drag_y) = match (mouse_position, self.last_mouse_position) {
+            (Some((x, y)), Some((last_x, last_y))) if dragging => (x - last_x, y - last_y),
+            _ => (0.0, 0.0),
+        };
+        self.last_mouse_position = mouse_position;
+
+        let dt = time.delta_seconds();
+        for (controller, follow) in (&mut controllers, &mut follows).join() {
+            if snap_pressed {
+                controller.mode = CameraMode::Follow;
+                controller.yaw = 0.0;
+            } else if mode_pressed {
+                controller.mode = controller.mode.next();
+                if controller.mode == CameraMode::Pan {
+                    // Start panning from wherever the camera is looking.
+                    if let Some(position) = follow.position {
+                        controller.pan_focus = position - follow.offset;
+                    }
+                }
+            }
+
+            let distance = controller.distance * (1.0 - zoom * controller.zoom_speed);
+            controller.distance = distance
+                .max(controller.min_distance)
+                .min(controller.max_distance);
+
+            match controller.mode {
+                CameraMode::Follow => {}
+                CameraMode::Orbit => {
+                    controller.yaw += orbit * controller.orbit_speed * dt;
+                    controller.yaw -= drag_x * DRAG_ORBIT_SPEED;
+                }
+                CameraMode::Pan => {
+                    let (right, forward) = controller.ground_axes();
+
+                    // Edge scrolling
+                    let mut scroll_x = 0.0;
+                    let mut scroll_y = 0.0;
+                    if let Some((x, y)) = mouse_position {
+                        let margin = controller.edge_scroll_margin;
+                        if x < margin {
+                            scroll_x -= 1.0;
+                        } else if x > screen_dimensions.width() - margin {
+                            scroll_x += 1.0;
+                        }
+                        if y < margin {
+                            scroll_y += 1.0;
+                        } else if y > screen_dimensions.height() - margin {
+                            scroll_y -= 1.0;
+                        }
+                    }
+                    let scroll: Vector3<f32> = (right * scroll_x + forward * scroll_y)
+                        * controller.pan_speed * dt;
+
+                    // Dragging moves the ground along with the mouse.
+                    let drag: Vector3<f32> = (right * -drag_x + forward * drag_y)
+                        * controller.distance * DRAG_PAN_SPEED;
+
+                    let mut pan_focus = controller.pan_focus + scroll + drag;
+                    if let Some(bounds) = &follow.bounds {
+                        pan_focus = bounds.clamp(pan_focus);
+                    }
+                    controller.pan_focus = pan_focus;
+                }
+            }
+
+            follow.focus_override = if controller.mode == CameraMode::Pan {
+                Some(controller.pan_focus)
+            } else {
+                None
+            };
+            follow.offset = controller.offset();
+            follow.rotation = controller.rotation();
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use amethyst;
+use amethyst::{
+    core::{
+        timing::Time,
+        transform::Transform,
+    },
+    derive::SystemDesc,
+    ecs::prelude::{
+        Entities, Entity, Join, Read, System, SystemData, WriteStorage,
+    },
+};
+use nalgebra::Vector3;
+
+use crate::camera::CameraFollow;
+
+// How quickly the estimate of the target's velocity responds to changes.
+const VELOCITY_SMOOTHING: f32 = 8.0;
+
+#[derive(Default, SystemDesc)]
+pub struct CameraFollowSystem;
+
+// Fraction of the remaining distance to cover this frame,
+//  for exponential smoothing which is independent of the frame rate.
+fn smoothing_factor(rate: f32, dt: f32) -> f32 {
+    1.0 - (-rate * dt).exp()
+}
+
+impl<'a> System<'a> for CameraFollowSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, CameraFollow>,
+        WriteStorage<'a, Transform>,
+        Read<'a, Time>,
+    );
+
+    fn run(&mut self, (entities, mut follows, mut transforms, time): Self::SystemData) {
+        let dt = time.delta_seconds();
+
+        let cameras: Vec<(Entity, Vector3<f32>)> = (&entities, &mut follows)
+            .join()
+            .filter_map(|(entity, follow)| {
+                let target_position =
+                    transforms.get(follow.target).map(|t| *t.translation());
+
+                if let Some(target_position) = target_position {
+                    if let Some(last_target_position) = follow.last_target_position {
+                        if dt > 0.0 {
+                            let velocity = (target_position - last_target_position) / dt;
+                            follow.velocity += (velocity - follow.velocity)
+                                * smoothing_factor(VELOCITY_SMOOTHING, dt);
+                        }
+                    }
+                    follow.last_target_position = Some(target_position);
+                }
+
+                let mut focus = match (follow.focus_override, target_position) {
+                    (Some(focus), _) => focus,
+                    (None, Some(target_position)) => {
+                        target_position + follow.velocity * follow.look_ahead
+                    }
+                    (None, None) => return None,
+                };
+                if let Some(bounds) = &follow.bounds {
+                    focus = bounds.clamp(focus);
+                }
+
+                Some((entity, focus + follow.offset))
+            })
+            .collect();
+
+        for (entity, desired_position) in cameras {
+            if let (Some(follow), Some(transform))
+            = (follows.get_mut(entity), transforms.get_mut(entity)) {
+                let position = follow.position.unwrap_or_else(|| *transform.translation());
+                let new_position = position
+                    + (desired_position - position) * smoothing_factor(follow.damping, dt);
+                follow.position = Some(new_position);
+
+                // Set the whole pose each frame,
+                //  so that effects (e.g. CameraShake) can be applied on top of it.
+                transform.set_translation(new_position);
+                transform.set_rotation(follow.rotation);
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use amethyst;
+use amethyst::{
+    core::{
+        timing::Time,
+        transform::Transform,
+    },
+    derive::SystemDesc,
+    ecs::prelude::{
+        Join, Read, ReadStorage, System, SystemData, WriteStorage,
+    },
+    shrev::{EventChannel, ReaderId},
+};
+use nalgebra::Vector3;
+
+use crate::camera::{CameraFollow, CameraShake};
+use crate::events::GameplayEvent;
+use crate::player::Player;
+use crate::states::GameplayState;
+
+// Trauma added when the player fires.
+const FIRED_TRAUMA: f32 = 0.2;
+// Trauma added when the player is damaged.
+const DAMAGED_TRAUMA: f32 = 0.4;
+// Explosions further than this from the camera's target add no trauma.
+const EXPLOSION_RADIUS: f32 = 30.0;
+
+#[derive(SystemDesc)]
+#[system_desc(name(CameraShakeSystemDesc))]
+pub struct CameraShakeSystem {
+    #[system_desc(event_channel_reader)]
+    reader_id: ReaderId<GameplayEvent>,
+}
+
+impl CameraShakeSystem {
+    pub fn new(reader_id: ReaderId<GameplayEvent>) -> Self {
+        CameraShakeSystem { reader_id }
+    }
+}
+
+// Trauma caused by an event, for a camera following `target_position`.
+fn event_trauma(
+    event: &GameplayEvent,
+    players: &ReadStorage<'_, Player>,
+    target_position: Option<Vector3<f32>>,
+) -> f32 {
+    match event {
+        GameplayEvent::Fired { entity, .. } if players.contains(*entity) => FIRED_TRAUMA,
+        GameplayEvent::Damaged { entity, .. } if players.contains(*entity) => DAMAGED_TRAUMA,
+        GameplayEvent::Explosion { position, strength } => {
+            let distance = target_position
+                .map(|target| (position - target).norm())
+                .unwrap_or(0.0);
+            let falloff = (1.0 - distance / EXPLOSION_RADIUS).max(0.0);
+            strength * falloff
+        }
+        _ => 0.0,
+    }
+}
+
+// Runs after the CameraFollowSystem, offsetting the pose it set.
+impl<'a> System<'a> for CameraShakeSystem {
+    type SystemData = (
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, CameraFollow>,
+        WriteStorage<'a, CameraShake>,
+        WriteStorage<'a, Transform>,
+        Read<'a, EventChannel<GameplayEvent>>,
+        Read<'a, Time>,
+        Read<'a, GameplayState>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            players,
+            follows,
+            mut shakes,
+            mut transforms,
+            events,
+            time,
+            gameplay_state,
+        ): Self::SystemData
+    ) {
+        // The camera holds still while the game is paused;
+        //  the trauma (and any events) are kept until it resumes.
+        if *gameplay_state != GameplayState::Running {
+            return;
+        }
+        let events: Vec<GameplayEvent> = events.read(&mut self.reader_id).cloned().collect();
+        let dt = time.delta_seconds();
+
+        for (follow, shake) in (&follows, &mut shakes).join() {
+            let target_position = transforms
+                .get(follow.target)
+                .map(|transform| *transform.translation());
+            for event in &events {
+                shake.add_trauma(event_trauma(event, &players, target_position));
+            }
+            shake.update(dt);
+        }
+
+        for (shake, transform) in (&shakes, &mut transforms).join() {
+            if shake.trauma <= 0.0 {
+                continue;
+            }
+            let (offset, rotation) = shake.displacement();
+            transform.prepend_translation(offset);
+            transform.set_rotation(transform.rotation() * rotation);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use amethyst;
+use amethyst::{
+    core::transform::Transform,
+    derive::SystemDesc,
+    ecs::prelude::{Read, ReadStorage, System, SystemData, Write, WriteStorage},
+    shrev::EventChannel,
+};
+
+use crate::collision::{contact, Collider};
+use crate::events::GameplayEvent;
+use crate::spatial_grid::SpatialGrid;
+
+// Finds overlapping colliders, sending a Collided event (to each of the pair) for each overlap.
+//
+// Overlapping solid colliders (e.g. tanks) are pushed apart, half the overlap each.
+//
+// The SpatialGrid (which indexes colliders' bounds) is the broad phase,
+//  so this runs after the SpatialGridSystem.
+#[derive(Default, SystemDesc)]
+pub struct CollisionSystem;
+
+impl<'a> System<'a> for CollisionSystem {
+    type SystemData = (
+        Read<'a, SpatialGrid>,
+        ReadStorage<'a, Collider>,
+        WriteStorage<'a, Transform>,
+        Write<'a, EventChannel<GameplayEvent>>,
+    );
+
+    fn run(&mut self, (grid, colliders, mut transforms, mut events): Self::SystemData) {
+        // Broad phase: colliders can only overlap if their bounds share a map tile.
+        for (entity_a, entity_b) in grid.candidate_pairs() {
+            let pair = (colliders.get(entity_a), colliders.get(entity_b));
+            let (collider_a, collider_b) = match pair {
+                (Some(a), Some(b)) => (a, b),
+                _ => continue,
+            };
+            // Placed from the current transforms, as earlier pairs may have been pushed apart.
+            let (shape_a, shape_b) = match (transforms.get(entity_a), transforms.get(entity_b)) {
+                (Some(a), Some(b)) => (collider_a.placed(a), collider_b.placed(b)),
+                _ => continue,
+            };
+
+            // Narrow phase
+            let contact = match contact(&shape_a, &shape_b) {
+                Some(contact) => contact,
+                None => continue,
+            };
+
+            events.single_write(GameplayEvent::Collided {
+                entity: entity_a,
+                other: entity_b,
+                normal: contact.normal,
+                depth: contact.depth,
+            });
+            events.single_write(GameplayEvent::Collided {
+                entity: entity_b,
+                other: entity_a,
+                normal: -contact.normal,
+                depth: contact.depth,
+            });
+
+            if collider_a.solid && collider_b.solid {
+                let push = contact.normal * (contact.depth / 2.0);
+                if let Some(transform) = transforms.get_mut(entity_a) {
+                    transform.prepend_translation_x(-push.x);
+                    transform.prepend_translation_z(-push.y);
+                }
+                if let Some(transform) = transforms.get_mut(entity_b) {
+                    transform.prepend_translation_x(push.x);
+                    transform.prepend_translation_z(push.y);
+                }
+            }
+        }
+    }
+}
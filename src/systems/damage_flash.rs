@@ -0,0 +1,57 @@
+use amethyst;
+use amethyst::{
+    derive::SystemDesc,
+    ecs::prelude::{Read, System, SystemData, WriteStorage},
+    shrev::{EventChannel, ReaderId},
+};
+
+use crate::assets::AssetRegistry;
+use crate::events::GameplayEvent;
+use crate::replace_material::{MaterialOverride, ReplaceMaterial, HIT_FLASH_MATERIAL};
+
+const HIT_FLASH_SOURCE: &str = "hit_flash";
+// Above longer-lasting looks (e.g. frozen or damaged), so the flash is always seen.
+const HIT_FLASH_PRIORITY: i32 = 100;
+const HIT_FLASH_DURATION: f32 = 0.1;
+
+// Flashes entities white when they're damaged,
+//  with a temporary override of their materials.
+#[derive(SystemDesc)]
+#[system_desc(name(DamageFlashSystemDesc))]
+pub struct DamageFlashSystem {
+    #[system_desc(event_channel_reader)]
+    reader_id: ReaderId<GameplayEvent>,
+}
+
+impl DamageFlashSystem {
+    pub fn new(reader_id: ReaderId<GameplayEvent>) -> Self {
+        DamageFlashSystem { reader_id }
+    }
+}
+
+impl<'a> System<'a> for DamageFlashSystem {
+    type SystemData = (
+        WriteStorage<'a, ReplaceMaterial>,
+        Read<'a, EventChannel<GameplayEvent>>,
+        // Not available until the assets have been loaded
+        Option<Read<'a, AssetRegistry>>,
+    );
+
+    fn run(&mut self, (mut replacements, events, registry): Self::SystemData) {
+        let flash_material = registry
+            .as_ref()
+            .and_then(|registry| registry.material(HIT_FLASH_MATERIAL));
+
+        for event in events.read(&mut self.reader_id) {
+            if let (GameplayEvent::Damaged { entity, .. }, Some(material)) = (event, &flash_material) {
+                if let Some(replacement) = replacements.get_mut(*entity) {
+                    replacement.push_override(
+                        MaterialOverride::new(HIT_FLASH_SOURCE, HIT_FLASH_PRIORITY)
+                            .with_material(material.clone())
+                            .with_duration(HIT_FLASH_DURATION),
+                    );
+                }
+            }
+        }
+    }
+}
@@ -1,53 +1,65 @@
 use amethyst;
 use amethyst::{
-    core::{timing::{Time}, Named, Parent},
+    core::timing::Time,
     derive::SystemDesc,
-    ecs::prelude::{Read, ReadStorage, System, SystemData},
+    ecs::prelude::{Entity, Read, System, SystemData, WriteStorage},
     input::{InputHandler, StringBindings},
+    ui::{UiFinder, UiText},
 };
 
-use crate::player::Player;
+use crate::logging::LogHistory;
 
+// How many of the most recent log messages the overlay shows.
+const OVERLAY_LINES: usize = 16;
+
+// Shows the most recent log messages in the debug overlay,
+//  which the "debug_overlay" action (F3) toggles.
 #[derive(Default, SystemDesc)]
-pub struct DebugSystem;
+pub struct DebugSystem {
+    toggle_was_down: bool,
+    visible: bool,
+    overlay_text: Option<Entity>,
+}
 
 impl<'a> System<'a> for DebugSystem {
     type SystemData = (
         Read<'a, InputHandler<StringBindings>>,
         Read<'a, Time>,
-        ReadStorage<'a, Named>,
-        ReadStorage<'a, Parent>,
-        ReadStorage<'a, Player>,
+        Option<Read<'a, LogHistory>>,
+        WriteStorage<'a, UiText>,
+        UiFinder<'a>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (
-            _input,
-            _time,
-            _named,
-            _parents,
-            _players
-        ) =
-            data;
-
-        // It's not that this is useless;
-        //  but it's far too verbose.
-        // Need to check for System Events or something
-        //  to filter all the noise.
-
-        // println!("==== Debug System ====");
-        // println!("query: named AND material AND mesh_data");
-        // for (name, _mat, _mesh) in (&named, &materials, &meshes).join() {
-        //     println!("- found enitity (w/ name, material, mesh): {}", name.name);
-        // }
-        // println!("query: has_parent");
-        // for (parent) in (&parents).join() {
-        //     println!("- found enitity (w/ parent)");
-        // }
-        // println!("query: named AND has_parent");
-        // for (name, parent) in (&named, &parents).join() {
-        //     println!("- found enitity (w/ name, parent): {}", name.name);
-        // }
-        // println!("==== Debug System END ====");
+        let (input, time, log_history, mut ui_text, finder) = data;
+
+        let toggle_down = input.action_is_down("debug_overlay").unwrap_or(false);
+        let toggle_pressed = toggle_down && !self.toggle_was_down;
+        self.toggle_was_down = toggle_down;
+        if toggle_pressed {
+            self.visible = !self.visible;
+        }
+
+        // The overlay is re-created each time the level is (re)started.
+        let overlay_is_stale = self.overlay_text
+            .map_or(true, |overlay_entity| ui_text.get(overlay_entity).is_none());
+        if overlay_is_stale {
+            self.overlay_text = finder.find("debug_overlay_text");
+        }
+        let overlay = match self.overlay_text.and_then(|entity| ui_text.get_mut(entity)) {
+            Some(overlay) => overlay,
+            None => return,
+        };
+
+        if !self.visible {
+            if !overlay.text.is_empty() {
+                overlay.text.clear();
+            }
+        } else if toggle_pressed || time.frame_number() % 10 == 0 {
+            overlay.text = match log_history {
+                Some(log_history) => log_history.recent(OVERLAY_LINES).join("\n"),
+                None => String::from("(no log history)"),
+            };
+        }
     }
 }
@@ -0,0 +1,49 @@
+use amethyst;
+use amethyst::{
+    derive::SystemDesc,
+    ecs::prelude::{Read, System, SystemData, WriteStorage},
+    shrev::{EventChannel, ReaderId},
+};
+
+use crate::animation::{AnimationController, RECOIL_CLIP};
+use crate::events::GameplayEvent;
+use crate::states::GameplayState;
+
+// Plays the recoil animation of units which fire.
+//
+// Its events wait while the game is paused, like the ProjectileSystem's.
+#[derive(SystemDesc)]
+#[system_desc(name(FireAnimationSystemDesc))]
+pub struct FireAnimationSystem {
+    #[system_desc(event_channel_reader)]
+    reader_id: ReaderId<GameplayEvent>,
+}
+
+impl FireAnimationSystem {
+    pub fn new(reader_id: ReaderId<GameplayEvent>) -> Self {
+        FireAnimationSystem { reader_id }
+    }
+}
+
+impl<'a> System<'a> for FireAnimationSystem {
+    type SystemData = (
+        Read<'a, EventChannel<GameplayEvent>>,
+        WriteStorage<'a, AnimationController>,
+        Read<'a, GameplayState>,
+    );
+
+    fn run(&mut self, (events, mut controllers, gameplay_state): Self::SystemData) {
+        if *gameplay_state != GameplayState::Running {
+            return;
+        }
+        for event in events.read(&mut self.reader_id) {
+            if let GameplayEvent::Fired { entity, .. } = event {
+                if let Some(controller) = controllers.get_mut(*entity) {
+                    if controller.has_clip(RECOIL_CLIP) {
+                        controller.play(RECOIL_CLIP);
+                    }
+                }
+            }
+        }
+    }
+}
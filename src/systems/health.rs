@@ -0,0 +1,86 @@
+use amethyst;
+use amethyst::{
+    core::{transform::Transform, ParentHierarchy},
+    derive::SystemDesc,
+    ecs::prelude::{
+        Entities, Join, ReadExpect, ReadStorage, System, SystemData, Write, WriteStorage,
+    },
+    shrev::{EventChannel, ReaderId},
+};
+use log::{error, info};
+
+use crate::events::GameplayEvent;
+use crate::player::Player;
+use crate::unit::Health;
+
+// Strength of the explosion when a unit is destroyed.
+const DESTROYED_EXPLOSION_STRENGTH: f32 = 1.0;
+
+// Applies damage to units' Health,
+//  destroying (with an explosion) the units which run out.
+//
+// The player's unit is left in place when it runs out.
+#[derive(SystemDesc)]
+#[system_desc(name(HealthSystemDesc))]
+pub struct HealthSystem {
+    #[system_desc(event_channel_reader)]
+    reader_id: ReaderId<GameplayEvent>,
+}
+
+impl HealthSystem {
+    pub fn new(reader_id: ReaderId<GameplayEvent>) -> Self {
+        HealthSystem { reader_id }
+    }
+}
+
+impl<'a> System<'a> for HealthSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Health>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Transform>,
+        ReadExpect<'a, ParentHierarchy>,
+        Write<'a, EventChannel<GameplayEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (entities, mut healths, players, transforms, hierarchy, mut events): Self::SystemData
+    ) {
+        let mut explosions = Vec::new();
+        for event in events.read(&mut self.reader_id) {
+            let (entity, amount) = match event {
+                GameplayEvent::Damaged { entity, amount } => (*entity, *amount),
+                _ => continue,
+            };
+            let health = match healths.get_mut(entity) {
+                Some(health) if !health.is_destroyed() => health,
+                _ => continue,
+            };
+            health.current = (health.current - amount).max(0.0);
+            if !health.is_destroyed() {
+                continue;
+            }
+
+            if let Some(transform) = transforms.get(entity) {
+                explosions.push(GameplayEvent::Explosion {
+                    position: *transform.translation(),
+                    strength: DESTROYED_EXPLOSION_STRENGTH,
+                });
+            }
+            if players.contains(entity) {
+                info!("The player's unit was destroyed");
+                continue;
+            }
+            // The unit's glTF scene nodes go with it.
+            let descendants = hierarchy.all_children(entity);
+            let nodes: Vec<_> = (&entities, &descendants).join().map(|(node, _)| node).collect();
+            for node in nodes.into_iter().chain(Some(entity)) {
+                if let Err(err) = entities.delete(node) {
+                    error!("Failed deleting destroyed unit: {}", err);
+                }
+            }
+        }
+        events.iter_write(explosions);
+    }
+}
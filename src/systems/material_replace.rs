@@ -1,49 +1,156 @@
-use std::borrow::Cow;
-use std::collections::HashMap;
+use std::iter;
 
 use amethyst;
 use amethyst::{
     assets::Handle,
     core::{
+        timing::Time,
         Named,
         Parent,
+        ParentHierarchy,
     },
     derive::SystemDesc,
-    ecs::prelude::{
-        Entities, Entity, Join, ReadStorage, System, SystemData, WriteStorage,
+    ecs::{
+        prelude::{
+            Entities, Entity, Join, Read, ReadExpect, ReadStorage, System, SystemData,
+            WriteStorage,
+        },
+        storage::ComponentEvent,
+        BitSet,
     },
-    renderer::Material,
+    renderer::{resources::Tint, Material},
+    shrev::ReaderId,
 };
+use log::debug;
 
-use crate::replace_material::ReplaceMaterial;
+use crate::replace_material::{
+    Appearance,
+    MaterialRule,
+    ReplaceMaterial,
+    ReplacedNode,
+};
+use crate::states::GameplayState;
 
-#[derive(Default, SystemDesc)]
-pub struct ReplaceMaterialSystem;
+// Applies ReplaceMaterials' rules and overrides to their descendants.
+//
+// Only examines nodes as they're added to a hierarchy (from the Parent storage's events),
+//  rather than every node each frame, so it scales with the number of tanks.
+#[derive(SystemDesc)]
+#[system_desc(name(ReplaceMaterialSystemDesc))]
+pub struct ReplaceMaterialSystem {
+    #[system_desc(flagged_storage_reader(Parent))]
+    parent_events: ReaderId<ComponentEvent>,
+}
 
-fn has_ancestor_with_name<'a>(
-    name: &Cow<'static, str>,
-    component: &Parent,
-    named: &ReadStorage<'a, Named>,
+impl ReplaceMaterialSystem {
+    pub fn new(parent_events: ReaderId<ComponentEvent>) -> Self {
+        ReplaceMaterialSystem { parent_events }
+    }
+}
+
+// The closest ancestor of an entity which has a ReplaceMaterial.
+fn replacing_ancestor<'a>(
+    entity: Entity,
+    replacements: &WriteStorage<'a, ReplaceMaterial>,
     parents: &ReadStorage<'a, Parent>
-) -> bool {
-    let mut parent = Some(component.entity);
+) -> Option<Entity> {
+    let mut parent = parents.get(entity).map(|p| p.entity);
 
-    println!("has ancestor with name={}?:", name);
     while let Some(parent_entity) = parent {
-        println!("- found parent");
-        if let Some(parent_name) = named.get(parent_entity).map(|n| &n.name) {
-            println!("-- parent has name: {}", parent_name);
+        if replacements.contains(parent_entity) {
+            return Some(parent_entity)
+        }
+        parent = parents.get(parent_entity).map(|p| p.entity);
+    }
+
+    None
+}
 
-            if name == parent_name {
-                println!("-- MATCHES!");
-                return true
+// Whether a rule applies to a node, given its name.
+fn rule_matches(rule: &MaterialRule, name: Option<&Named>) -> bool {
+    let name = name.map(|name| name.name.as_ref());
+    if let Some(pattern) = &rule.name {
+        if !name.map_or(false, |name| pattern.matches(name)) {
+            return false;
+        }
+    }
+    if let Some(original_nodes) = &rule.original_nodes {
+        if !name.map_or(false, |name| original_nodes.contains(name)) {
+            return false;
+        }
+    }
+    true
+}
+
+// Nodes which are no longer descendants of the ReplaceMaterial which matched them,
+//  along with that ReplaceMaterial's entity.
+//
+// Only the re-parented or detached entities (`changed`) and their descendants are checked;
+//  deleted ones are skipped, as their nodes are dropped when the ReplaceMaterial updates.
+fn moved_nodes<'a>(
+    entities: &Entities<'a>,
+    replacements: &WriteStorage<'a, ReplaceMaterial>,
+    parents: &ReadStorage<'a, Parent>,
+    hierarchy: &ParentHierarchy,
+    changed: &BitSet,
+) -> Vec<(Entity, Entity)> {
+    let mut moved = Vec::new();
+    for (entity, _) in (entities, changed).join() {
+        let descendants = hierarchy.all_children(entity);
+        let nodes = iter::once(entity)
+            .chain((entities, &descendants).join().map(|(node, _)| node));
+        for node in nodes {
+            let still_replaced = replacing_ancestor(node, replacements, parents)
+                .and_then(|replacer| replacements.get(replacer))
+                .map_or(false, |replacement| replacement.replaced.contains_key(&node));
+            if still_replaced {
+                continue;
+            }
+            let old_replacer = (entities, replacements)
+                .join()
+                .find(|(_, replacement)| replacement.replaced.contains_key(&node));
+            if let Some((replacer, _)) = old_replacer {
+                moved.push((replacer, node));
             }
         }
+    }
+    moved
+}
 
-        parent = parents.get(parent_entity).map(|p| p.entity);
+fn set_appearance<'a>(
+    entity: Entity,
+    appearance: Appearance,
+    materials: &mut WriteStorage<'a, Handle<Material>>,
+    tints: &mut WriteStorage<'a, Tint>,
+) -> () {
+    materials
+        .insert(entity, appearance.material)
+        .expect("entity is alive");
+    match appearance.tint {
+        Some(tint) => {
+            tints.insert(entity, tint).expect("entity is alive");
+        }
+        None => {
+            tints.remove(entity);
+        }
     }
+}
 
-    false
+// A node's look from the first matching rule, or its original look if none match.
+fn base_appearance(
+    replacement: &ReplaceMaterial,
+    name: Option<&Named>,
+    original: &Appearance,
+) -> Appearance {
+    for rule in replacement.all_rules() {
+        if rule_matches(rule, name) {
+            return Appearance {
+                material: rule.replacement.clone().unwrap_or_else(|| original.material.clone()),
+                tint: rule.tint.or(original.tint),
+            };
+        }
+    }
+    original.clone()
 }
 
 impl<'a> System<'a> for ReplaceMaterialSystem {
@@ -52,55 +159,449 @@ impl<'a> System<'a> for ReplaceMaterialSystem {
         WriteStorage<'a, ReplaceMaterial>,
         ReadStorage<'a, Named>,
         ReadStorage<'a, Parent>,
+        ReadExpect<'a, ParentHierarchy>,
         WriteStorage<'a, Handle<Material>>,
+        WriteStorage<'a, Tint>,
+        Read<'a, Time>,
+        Read<'a, GameplayState>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (entities, mut replacements, named, parents, mut materials) =
-            data;
-
-        let mut replacement_map: HashMap<Entity, Handle<Material>> =
-            HashMap::new();
-
-        // Build map of entities with the material we want to replace
-        //
-        // Outer loop: loop over ReplaceMaterials
-        // Inner loop: loop over named entities with material to find descendants
-        for (replacement_name, replacement)
-        in (&named, &mut replacements).join() {
-            if let Some(replacement_material) = &replacement.replacement {
-                let targets = &mut replacement.targets;
-
-                for (entity, name, parent, _material)
-                in (&entities, &named, &parents, &materials).join() {
-                    let name = &name.name;
-
-                    if targets.contains(name) {
-                        let is_descendant =
-                            has_ancestor_with_name(
-                                &replacement_name.name,
-                                parent,
-                                &named,
-                                &parents
-                            );
-                        if is_descendant {
-                            println!("found entity to replace material");
-                            replacement_map.insert(entity, replacement_material.clone());
-
-                            targets.remove(name);
-                        }
-                    }
+        let (
+            entities,
+            mut replacements,
+            named,
+            parents,
+            hierarchy,
+            mut materials,
+            mut tints,
+            time,
+            gameplay_state,
+        ) = data;
+
+        // Nodes added to a hierarchy (e.g. glTF scene nodes as they're spawned)...
+        let mut nodes: Vec<Entity> = Vec::new();
+        let mut reparented = BitSet::new();
+        for event in parents.channel().read(&mut self.parent_events) {
+            match event {
+                ComponentEvent::Inserted(id) => nodes.push(entities.entity(*id)),
+                ComponentEvent::Modified(id) => {
+                    nodes.push(entities.entity(*id));
+                    reparented.add(*id);
                 }
+                ComponentEvent::Removed(id) => {
+                    reparented.add(*id);
+                }
+            }
+        }
+        // Nodes moved out of a ReplaceMaterial's hierarchy get their own look back,
+        //  and are matched again (by the ReplaceMaterial they've moved to, if any).
+        let moved = moved_nodes(&entities, &replacements, &parents, &hierarchy, &reparented);
+        for (replacer, node) in moved {
+            let replaced = replacements
+                .get_mut(replacer)
+                .and_then(|replacement| replacement.replaced.remove(&node));
+            if let Some(replaced) = replaced {
+                set_appearance(node, replaced.original, &mut materials, &mut tints);
+                nodes.push(node);
+            }
+        }
+        // ...and the existing descendants of newly added ReplaceMaterials.
+        for (entity, replacement) in (&entities, &mut replacements).join() {
+            if !replacement.descendants_scanned {
+                replacement.descendants_scanned = true;
+                let descendants = hierarchy.all_children(entity);
+                nodes.extend((&entities, &descendants).join().map(|(node, _)| node));
+            }
+        }
 
-                if targets.is_empty() {
-                    replacement.replacement = None;
+        for entity in nodes {
+            if !entities.is_alive(entity) {
+                continue;
+            }
+            let material = match materials.get(entity) {
+                Some(material) => material,
+                None => continue,
+            };
+            let replacer = match replacing_ancestor(entity, &replacements, &parents) {
+                Some(replacer) => replacer,
+                None => continue,
+            };
+            let replacement = replacements
+                .get_mut(replacer)
+                .expect("ancestor has a ReplaceMaterial");
+            if replacement.replaced.contains_key(&entity) {
+                continue;
+            }
+
+            let original = Appearance {
+                material: material.clone(),
+                tint: tints.get(entity).cloned(),
+            };
+            let base = base_appearance(replacement, named.get(entity), &original);
+            debug!(
+                "matched node {} ({:?})",
+                named.get(entity).map_or("(unnamed)", |named| named.name.as_ref()),
+                entity,
+            );
+            replacement.replaced.insert(entity, ReplacedNode { original, base });
+            replacement.dirty = true;
+        }
+
+        // Timed overrides (e.g. hit flashes) don't run out while the game is paused.
+        let dt = if *gameplay_state == GameplayState::Running {
+            time.delta_seconds()
+        } else {
+            0.0
+        };
+        let mut appearance_list: Vec<(Entity, Appearance)> = Vec::new();
+        for replacement in (&mut replacements).join() {
+            replacement.update_overrides(dt);
+
+            if replacement.rules_changed {
+                let bases: Vec<(Entity, Appearance)> = replacement.replaced
+                    .iter()
+                    .map(|(node, replaced)| {
+                        (*node, base_appearance(replacement, named.get(*node), &replaced.original))
+                    })
+                    .collect();
+                for (node, base) in bases {
+                    if let Some(replaced) = replacement.replaced.get_mut(&node) {
+                        replaced.base = base;
+                    }
                 }
+                replacement.rules_changed = false;
+                replacement.dirty = true;
+            }
+
+            if !replacement.dirty {
+                continue;
+            }
+            replacement.dirty = false;
+            replacement.replaced.retain(|node, _| entities.is_alive(*node));
+
+            let top_override = replacement.top_override();
+            for (node, replaced) in &replacement.replaced {
+                let appearance = match top_override {
+                    // An override with its own material also replaces the tint.
+                    Some(material_override) if material_override.material.is_some() => Appearance {
+                        material: material_override.material.clone().unwrap(),
+                        tint: material_override.tint,
+                    },
+                    Some(material_override) => Appearance {
+                        material: replaced.base.material.clone(),
+                        tint: material_override.tint.or(replaced.base.tint),
+                    },
+                    None => replaced.base.clone(),
+                };
+                appearance_list.push((*node, appearance));
             }
         }
 
         // Replace the material
-        for (entity, material_handle) in replacement_map {
-            materials.insert(entity, material_handle);
+        for (entity, appearance) in appearance_list {
+            set_appearance(entity, appearance, &mut materials, &mut tints);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amethyst::{
+        assets::AssetStorage,
+        core::SystemDesc,
+        ecs::{Builder, RunNow, World, WorldExt},
+        renderer::palette::Srgba,
+        shrev::EventChannel,
+    };
+
+    use super::*;
+    use crate::assets::AssetRegistry;
+    use crate::events::GameplayEvent;
+    use crate::replace_material::{MaterialOverride, NamePattern};
+    use crate::systems::damage_flash::DamageFlashSystem;
+    use crate::systems::{DamageFlashSystemDesc, TeamColourSystem};
+    use crate::team::{Team, TeamColours, TeamPalette};
+
+    const BLUE: (f32, f32, f32, f32) = (0.2, 0.4, 1.0, 1.0);
+
+    // A headless world running the systems which change nodes' materials.
+    struct Scene {
+        world: World,
+        team_colour: TeamColourSystem,
+        damage_flash: DamageFlashSystem,
+        replace_material: ReplaceMaterialSystem,
+    }
+
+    impl Scene {
+        fn new() -> Self {
+            let mut world = World::new();
+            world.register::<Named>();
+            world.register::<Team>();
+            let team_colour = TeamColourSystem::default();
+            let damage_flash = DamageFlashSystemDesc::default().build(&mut world);
+            let replace_material = ReplaceMaterialSystemDesc::default().build(&mut world);
+            // Nodes are found from the Parent storage's events,
+            //  so the hierarchy itself isn't maintained here.
+            let hierarchy_events = world.write_storage::<Parent>().register_reader();
+            world.insert(ParentHierarchy::new(hierarchy_events));
+            world.insert(AssetStorage::<Material>::default());
+            world.insert(AssetRegistry::default());
+            world.insert(TeamPalette {
+                targets: Some(NamePattern::Exact("body".to_string())),
+                teams: vec![TeamColours {
+                    name: "blue".to_string(),
+                    material: None,
+                    tint: Some(BLUE),
+                }],
+            });
+            Scene { world, team_colour, damage_flash, replace_material }
         }
+
+        fn material(&self) -> Handle<Material> {
+            self.world.read_resource::<AssetStorage<Material>>().allocate()
+        }
+
+        // A tank, with a "body" and a "wheel" node using the given material.
+        fn tank(
+            &mut self,
+            replacement: ReplaceMaterial,
+            material: &Handle<Material>,
+        ) -> (Entity, Entity, Entity) {
+            let tank = self.world.create_entity().with(replacement).build();
+            let mut node = |name: &'static str| {
+                self.world
+                    .create_entity()
+                    .with(Parent { entity: tank })
+                    .with(Named::new(name))
+                    .with(material.clone())
+                    .build()
+            };
+            let body = node("body");
+            let wheel = node("wheel");
+            (tank, body, wheel)
+        }
+
+        fn tick(&mut self, dt: f32) -> () {
+            self.world.write_resource::<Time>().set_delta_seconds(dt);
+            self.team_colour.run_now(&self.world);
+            self.damage_flash.run_now(&self.world);
+            self.replace_material.run_now(&self.world);
+            self.world.maintain();
+        }
+
+        fn update<F: FnOnce(&mut ReplaceMaterial)>(&self, tank: Entity, update: F) -> () {
+            let mut replacements = self.world.write_storage::<ReplaceMaterial>();
+            update(replacements.get_mut(tank).expect("tank has a ReplaceMaterial"));
+        }
+
+        fn material_of(&self, node: Entity) -> Option<u32> {
+            self.world.read_storage::<Handle<Material>>().get(node).map(Handle::id)
+        }
+
+        fn tint_of(&self, node: Entity) -> Option<Srgba> {
+            self.world.read_storage::<Tint>().get(node).map(|tint| tint.0)
+        }
+    }
+
+    fn tint((r, g, b, a): (f32, f32, f32, f32)) -> Tint {
+        Tint(Srgba::new(r, g, b, a))
+    }
+
+    fn body_rule(replacement: Option<Handle<Material>>) -> MaterialRule {
+        MaterialRule {
+            name: Some(NamePattern::Exact("body".to_string())),
+            original_nodes: None,
+            replacement,
+            tint: None,
+        }
+    }
+
+    #[test]
+    fn rules_replace_matching_nodes() {
+        let mut scene = Scene::new();
+        let (paint, red) = (scene.material(), scene.material());
+        let rules = vec![body_rule(Some(red.clone()))];
+        let (_, body, wheel) = scene.tank(ReplaceMaterial::new(rules), &paint);
+        scene.tick(0.0);
+
+        assert_eq!(scene.material_of(body), Some(red.id()));
+        assert_eq!(scene.material_of(wheel), Some(paint.id()));
+    }
+
+    #[test]
+    fn reparented_nodes_are_matched_by_their_new_tank() {
+        let mut scene = Scene::new();
+        let (paint, red, green) = (scene.material(), scene.material(), scene.material());
+        let (_, body, _) = scene.tank(ReplaceMaterial::new(vec![body_rule(Some(red))]), &paint);
+        let (green_tank, _, _) =
+            scene.tank(ReplaceMaterial::new(vec![body_rule(Some(green.clone()))]), &paint);
+        scene.tick(0.0);
+
+        scene.world.write_storage::<Parent>().insert(body, Parent { entity: green_tank }).unwrap();
+        scene.tick(0.0);
+        assert_eq!(scene.material_of(body), Some(green.id()));
+
+        // Detached from any tank, it gets its own material back.
+        scene.world.write_storage::<Parent>().remove(body);
+        scene.tick(0.0);
+        assert_eq!(scene.material_of(body), Some(paint.id()));
+        let replacements = scene.world.read_storage::<ReplaceMaterial>();
+        assert!(!replacements.get(green_tank).unwrap().replaced.contains_key(&body));
+    }
+
+    #[test]
+    fn the_highest_priority_override_is_shown() {
+        let mut scene = Scene::new();
+        let (paint, frozen, flash) = (scene.material(), scene.material(), scene.material());
+        let (tank, body, wheel) = scene.tank(ReplaceMaterial::default(), &paint);
+        scene.tick(0.0);
+
+        scene.update(tank, |replacement| {
+            replacement.push_override(
+                MaterialOverride::new("damaged", 1).with_tint(tint((0.5, 0.5, 0.5, 1.0))),
+            );
+            replacement.push_override(
+                MaterialOverride::new("flash", 10).with_material(flash.clone()),
+            );
+            // Added last, but with a lower priority than the flash.
+            replacement.push_override(
+                MaterialOverride::new("frozen", 5).with_material(frozen.clone()),
+            );
+        });
+        scene.tick(0.0);
+        for node in &[body, wheel] {
+            assert_eq!(scene.material_of(*node), Some(flash.id()));
+            assert_eq!(scene.tint_of(*node), None);
+        }
+
+        scene.update(tank, |replacement| replacement.remove_override("flash"));
+        scene.tick(0.0);
+        assert_eq!(scene.material_of(body), Some(frozen.id()));
+
+        // A tint-only override keeps the nodes' own materials.
+        scene.update(tank, |replacement| replacement.remove_override("frozen"));
+        scene.tick(0.0);
+        assert_eq!(scene.material_of(body), Some(paint.id()));
+        assert_eq!(scene.tint_of(body), Some(Srgba::new(0.5, 0.5, 0.5, 1.0)));
+
+        scene.update(tank, |replacement| replacement.remove_override("damaged"));
+        scene.tick(0.0);
+        assert_eq!(scene.material_of(body), Some(paint.id()));
+        assert_eq!(scene.tint_of(body), None);
+    }
+
+    #[test]
+    fn the_latest_of_equal_priority_overrides_is_shown() {
+        let mut scene = Scene::new();
+        let (paint, first, second) = (scene.material(), scene.material(), scene.material());
+        let (tank, body, _) = scene.tank(ReplaceMaterial::default(), &paint);
+
+        scene.update(tank, |replacement| {
+            replacement.push_override(MaterialOverride::new("first", 1).with_material(first));
+            replacement.push_override(
+                MaterialOverride::new("second", 1).with_material(second.clone()),
+            );
+        });
+        scene.tick(0.0);
+
+        assert_eq!(scene.material_of(body), Some(second.id()));
+    }
+
+    #[test]
+    fn timed_overrides_expire() {
+        let mut scene = Scene::new();
+        let (paint, red, flash) = (scene.material(), scene.material(), scene.material());
+        let rules = vec![body_rule(Some(red.clone()))];
+        let (tank, body, wheel) = scene.tank(ReplaceMaterial::new(rules), &paint);
+        scene.tick(0.0);
+
+        scene.update(tank, |replacement| {
+            replacement.push_override(
+                MaterialOverride::new("flash", 1).with_material(flash.clone()).with_duration(0.1),
+            );
+        });
+        scene.tick(0.05);
+        assert_eq!(scene.material_of(body), Some(flash.id()));
+        assert_eq!(scene.material_of(wheel), Some(flash.id()));
+
+        // Back to the rules' result, rather than the original material.
+        scene.tick(0.06);
+        assert_eq!(scene.material_of(body), Some(red.id()));
+        assert_eq!(scene.material_of(wheel), Some(paint.id()));
+    }
+
+    #[test]
+    fn timed_overrides_wait_while_paused() {
+        let mut scene = Scene::new();
+        let (paint, flash) = (scene.material(), scene.material());
+        let (tank, body, _) = scene.tank(ReplaceMaterial::default(), &paint);
+        scene.tick(0.0);
+
+        scene.update(tank, |replacement| {
+            replacement.push_override(
+                MaterialOverride::new("flash", 1).with_material(flash.clone()).with_duration(0.1),
+            );
+        });
+        *scene.world.write_resource::<GameplayState>() = GameplayState::Paused;
+        scene.tick(1.0);
+        assert_eq!(scene.material_of(body), Some(flash.id()));
+
+        *scene.world.write_resource::<GameplayState>() = GameplayState::Running;
+        scene.tick(0.11);
+        assert_eq!(scene.material_of(body), Some(paint.id()));
+    }
+
+    #[test]
+    fn damage_flashes_the_unit() {
+        let mut scene = Scene::new();
+        let (paint, flash) = (scene.material(), scene.material());
+        scene.world.write_resource::<AssetRegistry>().insert_material("flash", flash.clone());
+        let (tank, body, _) = scene.tank(ReplaceMaterial::default(), &paint);
+        scene.tick(0.0);
+
+        scene.world
+            .write_resource::<EventChannel<GameplayEvent>>()
+            .single_write(GameplayEvent::Damaged { entity: tank, amount: 1.0 });
+        scene.tick(0.0);
+        assert_eq!(scene.material_of(body), Some(flash.id()));
+
+        scene.tick(0.2);
+        assert_eq!(scene.material_of(body), Some(paint.id()));
+    }
+
+    #[test]
+    fn teams_tint_their_units() {
+        let mut scene = Scene::new();
+        let paint = scene.material();
+        let (tank, body, wheel) = scene.tank(ReplaceMaterial::default(), &paint);
+        scene.world.write_storage::<Team>().insert(tank, Team::new("blue")).unwrap();
+        scene.tick(0.0);
+
+        assert_eq!(scene.material_of(body), Some(paint.id()));
+        assert_eq!(scene.tint_of(body), Some(tint(BLUE).0));
+        assert_eq!(scene.tint_of(wheel), None);
+    }
+
+    #[test]
+    fn reverting_removes_the_team_colours() {
+        let mut scene = Scene::new();
+        let (paint, red, flash) = (scene.material(), scene.material(), scene.material());
+        let (tank, body, _) = scene.tank(ReplaceMaterial::new(vec![body_rule(Some(red))]), &paint);
+        scene.world.write_storage::<Team>().insert(tank, Team::new("blue")).unwrap();
+        scene.tick(0.0);
+        scene.update(tank, |replacement| {
+            replacement.push_override(MaterialOverride::new("flash", 1).with_material(flash));
+        });
+        scene.tick(0.0);
+
+        scene.update(tank, ReplaceMaterial::revert);
+        // The TeamColourSystem has had a chance to re-apply the team's rules.
+        scene.tick(0.0);
+        scene.tick(0.0);
+        assert_eq!(scene.material_of(body), Some(paint.id()));
+        assert_eq!(scene.tint_of(body), None);
+        let replacements = scene.world.read_storage::<ReplaceMaterial>();
+        assert_eq!(replacements.get(tank).and_then(|r| r.team.clone()), None);
     }
 }
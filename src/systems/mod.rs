@@ -1,9 +1,37 @@
+mod animation_controller;
+mod camera_aspect;
+mod camera_control;
+mod camera_follow;
+mod camera_shake;
+mod collision;
+mod damage_flash;
 mod debug;
+mod fire_animation;
+mod health;
 mod material_replace;
+mod player_fire;
 mod player_movement;
+mod projectile;
+mod spatial_grid;
+mod team_colour;
+mod turret;
 mod ui;
 
+pub use self::animation_controller::AnimationControllerSystem;
+pub use self::camera_aspect::CameraAspectSystem;
+pub use self::camera_control::CameraControlSystem;
+pub use self::camera_follow::CameraFollowSystem;
+pub use self::camera_shake::CameraShakeSystemDesc;
+pub use self::collision::CollisionSystem;
+pub use self::damage_flash::DamageFlashSystemDesc;
 pub use self::debug::DebugSystem;
-pub use self::material_replace::ReplaceMaterialSystem;
+pub use self::fire_animation::FireAnimationSystemDesc;
+pub use self::health::HealthSystemDesc;
+pub use self::material_replace::ReplaceMaterialSystemDesc;
+pub use self::player_fire::PlayerFireSystem;
 pub use self::player_movement::MovementSystem;
+pub use self::projectile::ProjectileSystemDesc;
+pub use self::spatial_grid::SpatialGridSystem;
+pub use self::team_colour::TeamColourSystem;
+pub use self::turret::TurretSystem;
 pub use self::ui::UISystem;
@@ -0,0 +1,71 @@
+use amethyst;
+use amethyst::{
+    core::{
+        timing::Time,
+        transform::Transform,
+    },
+    derive::SystemDesc,
+    ecs::prelude::{
+        Entities, Join, Read, ReadStorage, System, SystemData, Write, WriteStorage,
+    },
+    input::{InputHandler, StringBindings},
+    shrev::EventChannel,
+};
+use nalgebra::Vector3;
+
+use crate::events::GameplayEvent;
+use crate::player::Player;
+use crate::turret::Turret;
+use crate::unit::Weapon;
+use crate::utils::{heading, yaw_of_direction};
+
+// How far the trigger must be pulled to fire.
+const TRIGGER_THRESHOLD: f64 = 0.5;
+
+#[derive(Default, SystemDesc)]
+pub struct PlayerFireSystem;
+
+impl<'s> System<'s> for PlayerFireSystem {
+    type SystemData = (
+        Entities<'s>,
+        ReadStorage<'s, Player>,
+        WriteStorage<'s, Weapon>,
+        ReadStorage<'s, Transform>,
+        ReadStorage<'s, Turret>,
+        Read<'s, InputHandler<StringBindings>>,
+        Read<'s, Time>,
+        Write<'s, EventChannel<GameplayEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities, players, mut weapons, transforms, turrets, input, time, mut events
+        ): Self::SystemData
+    ) {
+        let firing = input.action_is_down("fire").unwrap_or(false)
+            || input.axis_value("fire_trigger").unwrap_or(0.0) > TRIGGER_THRESHOLD;
+
+        for (entity, _, weapon, transform) in
+            (&entities, &players, &mut weapons, &transforms).join()
+        {
+            weapon.remaining = (weapon.remaining - time.delta_seconds()).max(0.0);
+            if !firing || weapon.remaining > 0.0 {
+                continue;
+            }
+            weapon.remaining = weapon.cooldown;
+
+            // Fire the way the turret faces (or the tank, until its turret is found).
+            let turret_yaw = turrets
+                .join()
+                .find(|turret| turret.tank == entity)
+                .map_or(0.0, |turret| turret.yaw);
+            let yaw = yaw_of_direction(&heading(transform)) + turret_yaw;
+            events.single_write(GameplayEvent::Fired {
+                entity,
+                position: *transform.translation(),
+                direction: Vector3::new(yaw.sin(), 0.0, yaw.cos()),
+            });
+        }
+    }
+}
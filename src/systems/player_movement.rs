@@ -1,5 +1,3 @@
-use std::f32::consts::PI;
-
 use amethyst;
 use amethyst::{
     core::{
@@ -11,11 +9,16 @@ use amethyst::{
         Join, Read, ReadStorage, System, WriteStorage
     },
     input::{InputHandler, StringBindings},
+    renderer::Camera,
 };
 use amethyst::ecs::SystemData;
 use nalgebra::Vector3;
 
+use crate::controls::{ControlScheme, ControlsConfig};
 use crate::player::Player;
+use crate::preferences::Preferences;
+use crate::unit::Movement;
+use crate::utils::{heading, wrap_angle, yaw_of_direction};
 
 #[derive(SystemDesc)]
 pub struct MovementSystem;
@@ -23,20 +26,174 @@ pub struct MovementSystem;
 impl<'s> System<'s> for MovementSystem {
     type SystemData = (
         ReadStorage<'s, Player>,
+        ReadStorage<'s, Movement>,
+        ReadStorage<'s, Camera>,
         WriteStorage<'s, Transform>,
         Read<'s, InputHandler<StringBindings>>,
+        Read<'s, ControlsConfig>,
+        Read<'s, Preferences>,
         Read<'s, Time>,
     );
 
-    fn run(&mut self, (players, mut transforms, input, time): Self::SystemData) {
-        let turn = input.axis_value("player_rotation").unwrap_or_else(|| 0.0);
-        let z_move = input.axis_value("player_acceleration").unwrap_or_else(|| 0.0);
+    fn run(
+        &mut self,
+        (
+            players,
+            movements,
+            cameras,
+            mut transforms,
+            input,
+            controls,
+            preferences,
+            time
+        ): Self::SystemData
+    ) {
+        // Axis values are analog (e.g. from a gamepad's stick),
+        //  so scale the movement by their magnitude.
+        let turn = controls.apply(
+            input.axis_value("player_rotation").unwrap_or_else(|| 0.0) as f32
+        );
+        let z_move = controls.apply(
+            input.axis_value("player_acceleration").unwrap_or_else(|| 0.0) as f32
+        );
 
         let dt = time.delta_seconds();
-        for (_, transform) in (&players, &mut transforms).join() {
-            let delta_z = z_move as f32 * 2.0 * dt;
-            transform.append_translation(Vector3::new(0.0, 0.0, delta_z));
-            transform.append_rotation_y_axis(turn as f32 * PI / 2.0 * dt); // <-- no yaw_local?
+        match preferences.control_scheme {
+            ControlScheme::Tank => {
+                for (_, movement, transform) in (&players, &movements, &mut transforms).join() {
+                    let delta_z = z_move * movement.speed * dt;
+                    transform.append_translation(Vector3::new(0.0, 0.0, delta_z));
+                    transform.append_rotation_y_axis(turn * movement.turn_rate * dt); // <-- no yaw_local?
+                }
+            }
+            ControlScheme::CameraRelative => {
+                // "Up" on the stick (or W) is away from the camera,
+                //  along the ground.
+                let camera_forward = (&cameras, &transforms)
+                    .join()
+                    .next()
+                    .map(|(_, camera_transform)| {
+                        let forward = camera_transform.rotation() * -Vector3::z();
+                        Vector3::new(forward.x, 0.0, forward.z)
+                    })
+                    .filter(|forward| forward.norm() > 0.0)
+                    .map(|forward| forward.normalize())
+                    .unwrap_or_else(|| -Vector3::z());
+                let camera_right = camera_forward.cross(&Vector3::y());
+
+                // The rotation axis is positive for turning left.
+                let direction = camera_right * -turn + camera_forward * z_move;
+                let magnitude = direction.norm().min(1.0);
+                if magnitude <= 0.0 {
+                    return;
+                }
+                let target_yaw = yaw_of_direction(&direction);
+
+                for (_, movement, transform) in (&players, &movements, &mut transforms).join() {
+                    let tank_heading = heading(transform);
+                    let difference =
+                        wrap_angle(target_yaw - yaw_of_direction(&tank_heading));
+
+                    let max_turn = movement.turn_rate * dt;
+                    transform.append_rotation_y_axis(difference.max(-max_turn).min(max_turn));
+
+                    // Only drive once roughly facing the direction.
+                    let speed = magnitude * difference.cos().max(0.0) * movement.speed;
+                    transform.append_translation(tank_heading * speed * dt);
+                }
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::path::Path;
+
+    use amethyst::{
+        ecs::{Builder, RunNow, World, WorldExt},
+        input::{Bindings, ControllerAxis, ControllerEvent, InputEvent},
+        shrev::EventChannel,
+    };
+    use ron::de::from_reader as from_ron_reader;
+
+    use super::*;
+
+    const SPEED: f32 = 2.0;
+    const TURN_RATE: f32 = 1.0;
+
+    // Move the player for a second, with the gamepad's left stick held at (x, y).
+    //
+    // The stick's events are sent through an InputHandler using the game's bindings.
+    fn drive(x: f32, y: f32) -> Transform {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("resources/input.ron");
+        let bindings: Bindings<StringBindings> =
+            from_ron_reader(File::open(path).expect("input.ron exists")).expect("input.ron parses");
+        let mut input = InputHandler::<StringBindings>::new();
+        input.bindings = bindings;
+        let mut input_events = EventChannel::<InputEvent<StringBindings>>::new();
+        let stick = |axis, value| ControllerEvent::ControllerAxisMoved { which: 0, axis, value };
+        let stick_events = vec![
+            ControllerEvent::ControllerConnected { which: 0 },
+            stick(ControllerAxis::LeftX, x),
+            stick(ControllerAxis::LeftY, y),
+        ];
+        for event in &stick_events {
+            input.send_controller_event(event, &mut input_events);
+        }
+
+        let mut world = World::new();
+        world.register::<Player>();
+        world.register::<Movement>();
+        world.register::<Camera>();
+        world.register::<Transform>();
+        world.insert(input);
+        world.insert(ControlsConfig::default());
+        world.insert(Preferences::default());
+        let mut time = Time::default();
+        time.set_delta_seconds(1.0);
+        world.insert(time);
+        let player = world
+            .create_entity()
+            .with(Player)
+            .with(Movement { speed: SPEED, turn_rate: TURN_RATE })
+            .with(Transform::default())
+            .build();
+
+        MovementSystem.run_now(&world);
+        let transforms = world.read_storage::<Transform>();
+        transforms.get(player).expect("player has a transform").clone()
+    }
+
+    fn assert_close(actual: f32, expected: f32) -> () {
+        assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn the_stick_drives_through_the_response_curve() {
+        // The bindings invert the stick's Y, so pushing it up (negative) drives forward.
+        let forward = ControlsConfig::default().apply(0.6) * SPEED;
+        assert_close(drive(0.0, -0.6).translation().z, forward);
+        assert_close(drive(0.0, 0.6).translation().z, -forward);
+        // Full deflection is full speed.
+        assert_close(drive(0.0, -1.0).translation().z, SPEED);
+    }
+
+    #[test]
+    fn the_stick_turns_through_the_response_curve() {
+        // Pushing the stick left (negative X) turns left, i.e. anticlockwise from above.
+        let transform = drive(-0.6, 0.0);
+        let turn = ControlsConfig::default().apply(0.6) * TURN_RATE;
+        assert_close(transform.rotation().angle(), turn);
+        assert!(heading(&transform).x > 0.0);
+        assert!(heading(&drive(0.6, 0.0)).x < 0.0);
+    }
+
+    #[test]
+    fn a_resting_stick_is_within_the_dead_zone() {
+        let transform = drive(0.1, -0.1);
+        assert_eq!(*transform.translation(), Vector3::zeros());
+        assert_eq!(transform.rotation().angle(), 0.0);
+    }
+}
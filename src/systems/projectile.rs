@@ -0,0 +1,186 @@
+use std::collections::HashSet;
+
+use amethyst;
+use amethyst::{
+    core::{
+        timing::Time,
+        transform::Transform,
+    },
+    derive::SystemDesc,
+    ecs::prelude::{
+        Entities, Join, Read, ReadStorage, System, SystemData, Write, WriteStorage,
+    },
+    renderer::{debug_drawing::DebugLines, palette::Srgba},
+    shrev::{EventChannel, ReaderId},
+};
+use log::error;
+use nalgebra::Point3;
+
+use crate::collision::{Collider, ColliderShape};
+use crate::events::GameplayEvent;
+use crate::level::LevelEntity;
+use crate::projectile::{Projectile, SHELL_HEIGHT, SHELL_LIFETIME, SHELL_RADIUS, SHELL_SPEED};
+use crate::spatial_grid::SpatialIndexed;
+use crate::states::GameplayState;
+use crate::unit::{Health, Weapon};
+
+// Strength of the explosion when a shell hits a unit.
+const HIT_EXPLOSION_STRENGTH: f32 = 0.3;
+// Seconds of flight which a shell's tracer line spans.
+const TRACER_DURATION: f32 = 0.05;
+
+fn draw_tracer(debug_lines: &mut DebugLines, projectile: &Projectile, transform: &Transform) {
+    let head = Point3::from(*transform.translation());
+    let tail = head - projectile.velocity * TRACER_DURATION;
+    debug_lines.draw_line(tail, head, Srgba::new(1.0, 0.8, 0.3, 1.0));
+}
+
+// Spawns a shell when a unit fires, moves shells (drawing them as tracer lines),
+//  and has them damage the units they hit.
+//
+// Like the `pausable(GameplayState::Running)` systems, shells don't move while paused
+//  (and its events wait until the game resumes); it checks the state itself,
+//  as `pausable` wraps a System rather than its SystemDesc.
+#[derive(SystemDesc)]
+#[system_desc(name(ProjectileSystemDesc))]
+pub struct ProjectileSystem {
+    #[system_desc(event_channel_reader)]
+    reader_id: ReaderId<GameplayEvent>,
+}
+
+impl ProjectileSystem {
+    pub fn new(reader_id: ReaderId<GameplayEvent>) -> Self {
+        ProjectileSystem { reader_id }
+    }
+}
+
+impl<'a> System<'a> for ProjectileSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Projectile>,
+        WriteStorage<'a, Transform>,
+        WriteStorage<'a, Collider>,
+        WriteStorage<'a, LevelEntity>,
+        WriteStorage<'a, SpatialIndexed>,
+        ReadStorage<'a, Weapon>,
+        ReadStorage<'a, Health>,
+        Read<'a, Time>,
+        Read<'a, GameplayState>,
+        Write<'a, DebugLines>,
+        Write<'a, EventChannel<GameplayEvent>>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            entities,
+            mut projectiles,
+            mut transforms,
+            mut colliders,
+            mut level_entities,
+            mut indexed,
+            weapons,
+            healths,
+            time,
+            gameplay_state,
+            mut debug_lines,
+            mut events,
+        ): Self::SystemData
+    ) {
+        if *gameplay_state != GameplayState::Running {
+            // Still draw the shells, where they stopped.
+            for (projectile, transform) in (&projectiles, &transforms).join() {
+                draw_tracer(&mut debug_lines, projectile, transform);
+            }
+            return;
+        }
+
+        let mut fired = Vec::new();
+        let mut hits = Vec::new();
+        for event in events.read(&mut self.reader_id) {
+            match event {
+                GameplayEvent::Fired { entity, position, direction } => {
+                    fired.push((*entity, *position, *direction));
+                }
+                GameplayEvent::Collided { entity, other, .. } => {
+                    let hits_unit = match projectiles.get(*entity) {
+                        Some(shell) => shell.owner != *other && healths.contains(*other),
+                        None => false,
+                    };
+                    if hits_unit {
+                        hits.push((*entity, *other));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut new_events = Vec::new();
+        let mut spent = HashSet::new();
+        for (shell, unit) in hits {
+            // A shell may touch more than one unit; it only hits the first.
+            if !spent.insert(shell) {
+                continue;
+            }
+            if let Some(projectile) = projectiles.get(shell) {
+                let amount = projectile.damage;
+                new_events.push(GameplayEvent::Damaged { entity: unit, amount });
+            }
+            if let Some(transform) = transforms.get(shell) {
+                new_events.push(GameplayEvent::Explosion {
+                    position: *transform.translation(),
+                    strength: HIT_EXPLOSION_STRENGTH,
+                });
+            }
+            if let Err(err) = entities.delete(shell) {
+                error!("Failed deleting shell: {}", err);
+            }
+        }
+        events.iter_write(new_events);
+
+        let dt = time.delta_seconds();
+        for (entity, projectile, transform) in
+            (&entities, &mut projectiles, &mut transforms).join()
+        {
+            if spent.contains(&entity) {
+                continue;
+            }
+            projectile.remaining -= dt;
+            if projectile.remaining <= 0.0 {
+                if let Err(err) = entities.delete(entity) {
+                    error!("Failed deleting shell: {}", err);
+                }
+                continue;
+            }
+            transform.prepend_translation(projectile.velocity * dt);
+            draw_tracer(&mut debug_lines, projectile, transform);
+        }
+
+        for (owner, position, direction) in fired {
+            let damage = match weapons.get(owner) {
+                Some(weapon) => weapon.damage,
+                None => continue,
+            };
+            let mut transform = Transform::default();
+            transform.set_translation_xyz(position.x, SHELL_HEIGHT, position.z);
+            let mut collider = Collider::new(ColliderShape::Circle { radius: SHELL_RADIUS });
+            collider.solid = false;
+            entities
+                .build_entity()
+                .with(transform, &mut transforms)
+                .with(collider, &mut colliders)
+                .with(LevelEntity, &mut level_entities)
+                .with(SpatialIndexed, &mut indexed)
+                .with(
+                    Projectile {
+                        owner,
+                        velocity: direction * SHELL_SPEED,
+                        damage,
+                        remaining: SHELL_LIFETIME,
+                    },
+                    &mut projectiles,
+                )
+                .build();
+        }
+    }
+}
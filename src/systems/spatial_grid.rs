@@ -0,0 +1,44 @@
+use amethyst;
+use amethyst::{
+    core::transform::Transform,
+    derive::SystemDesc,
+    ecs::prelude::{Entities, Join, ReadStorage, System, SystemData, Write},
+};
+use nalgebra::Vector2;
+
+use crate::collision::Collider;
+use crate::spatial_grid::{SpatialGrid, SpatialIndexed};
+
+fn ground_position(transform: &Transform) -> Vector2<f32> {
+    let translation = transform.translation();
+    Vector2::new(translation.x, translation.z)
+}
+
+// Rebuilds the SpatialGrid from where the SpatialIndexed entities
+//  (and those with a Collider, along with its bounds) are this frame.
+#[derive(Default, SystemDesc)]
+pub struct SpatialGridSystem;
+
+impl<'a> System<'a> for SpatialGridSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, SpatialIndexed>,
+        ReadStorage<'a, Collider>,
+        ReadStorage<'a, Transform>,
+        Write<'a, SpatialGrid>,
+    );
+
+    fn run(&mut self, (entities, indexed, colliders, transforms, mut grid): Self::SystemData) {
+        grid.clear();
+        // Only the indexed entities are visited, not every node with a Transform.
+        for (entity, transform, collider) in (&entities, &transforms, &colliders).join() {
+            let bounds = collider.placed(transform).bounds();
+            grid.insert_with_bounds(entity, ground_position(transform), bounds);
+        }
+        for (entity, transform, _, _) in
+            (&entities, &transforms, &indexed, !&colliders).join()
+        {
+            grid.insert(entity, ground_position(transform));
+        }
+    }
+}
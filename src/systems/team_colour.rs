@@ -0,0 +1,59 @@
+use amethyst;
+use amethyst::{
+    derive::SystemDesc,
+    ecs::prelude::{
+        Entities, Join, Read, ReadExpect, ReadStorage, System, SystemData, WriteStorage,
+    },
+};
+
+use crate::assets::AssetRegistry;
+use crate::replace_material::ReplaceMaterial;
+use crate::team::{Team, TeamPalette};
+
+// Gives entities with a Team their team's colours,
+//  by setting the team rules of their ReplaceMaterial.
+//
+// A ReplaceMaterial which has been reverted keeps its original look.
+//
+// Runs before the ReplaceMaterialSystem, which applies the rules.
+#[derive(Default, SystemDesc)]
+pub struct TeamColourSystem;
+
+impl<'a> System<'a> for TeamColourSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Team>,
+        WriteStorage<'a, ReplaceMaterial>,
+        ReadExpect<'a, TeamPalette>,
+        // Not available until the assets have been loaded
+        Option<Read<'a, AssetRegistry>>,
+    );
+
+    fn run(&mut self, (entities, teams, mut replacements, palette, registry): Self::SystemData) {
+        let registry = match registry {
+            Some(registry) => registry,
+            None => return,
+        };
+
+        for (entity, team) in (&entities, &teams).join() {
+            // Already coloured for its team, or its colours have been reverted.
+            let is_current = replacements.get(entity).map_or(false, |replacement| {
+                replacement.team_reverted || replacement.team.as_ref() == Some(&team.name)
+            });
+            if is_current {
+                continue;
+            }
+
+            // Also covers a change of team.
+            let rules = palette.rules(&team.name, &registry);
+            if !replacements.contains(entity) {
+                replacements
+                    .insert(entity, ReplaceMaterial::default())
+                    .expect("entity is alive");
+            }
+            if let Some(replacement) = replacements.get_mut(entity) {
+                replacement.set_team_rules(&team.name, rules);
+            }
+        }
+    }
+}
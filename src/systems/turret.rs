@@ -0,0 +1,161 @@
+use std::f32::consts::PI;
+
+use amethyst;
+use amethyst::{
+    core::{
+        geometry::Plane,
+        math::Point2,
+        timing::Time,
+        transform::Transform,
+        Named,
+        Parent,
+    },
+    derive::SystemDesc,
+    ecs::prelude::{
+        Entities, Entity, Join, Read, ReadExpect, ReadStorage, System, SystemData,
+        WriteStorage,
+    },
+    input::{InputHandler, StringBindings},
+    renderer::Camera,
+    window::ScreenDimensions,
+};
+use nalgebra::Vector3;
+
+use crate::controls::{ControlScheme, ControlsConfig};
+use crate::player::Player;
+use crate::preferences::Preferences;
+use crate::turret::{Turret, TURRET_NODE_NAME};
+use crate::utils::{heading, wrap_angle, yaw_of_direction};
+
+const TURN_RATE: f32 = PI / 2.0;
+
+#[derive(Default, SystemDesc)]
+pub struct TurretSystem;
+
+fn player_ancestor<'a>(
+    entity: Entity,
+    parents: &ReadStorage<'a, Parent>,
+    players: &ReadStorage<'a, Player>,
+) -> Option<Entity> {
+    let mut parent = parents.get(entity).map(|p| p.entity);
+
+    while let Some(parent_entity) = parent {
+        if players.contains(parent_entity) {
+            return Some(parent_entity)
+        }
+        parent = parents.get(parent_entity).map(|p| p.entity);
+    }
+
+    None
+}
+
+// The point on the ground plane under the mouse cursor.
+fn mouse_ground_position<'a>(
+    input: &InputHandler<StringBindings>,
+    screen_dimensions: &ScreenDimensions,
+    cameras: &ReadStorage<'a, Camera>,
+    transforms: &WriteStorage<'a, Transform>,
+) -> Option<Vector3<f32>> {
+    let (mouse_x, mouse_y) = input.mouse_position()?;
+    let (camera, camera_transform) = (cameras, transforms).join().next()?;
+
+    let ray = camera.projection().screen_ray(
+        Point2::new(mouse_x, mouse_y),
+        screen_dimensions.diagonal(),
+        camera_transform,
+    );
+    let distance = ray.intersect_plane(&Plane::with_y(0.0))?;
+    Some(ray.at_distance(distance).coords)
+}
+
+impl<'a> System<'a> for TurretSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Named>,
+        ReadStorage<'a, Parent>,
+        ReadStorage<'a, Player>,
+        ReadStorage<'a, Camera>,
+        WriteStorage<'a, Turret>,
+        WriteStorage<'a, Transform>,
+        Read<'a, InputHandler<StringBindings>>,
+        Read<'a, ControlsConfig>,
+        Read<'a, Preferences>,
+        ReadExpect<'a, ScreenDimensions>,
+        Read<'a, Time>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            entities,
+            named,
+            parents,
+            players,
+            cameras,
+            mut turrets,
+            mut transforms,
+            input,
+            controls,
+            preferences,
+            screen_dimensions,
+            time,
+        ) = data;
+
+        // The glTF scene's nodes are spawned some time after the Player,
+        //  so look for the turret node until it's found;
+        //  after that, the Turret component on it is all that's joined over.
+        let turretless_player = (&entities, &players)
+            .join()
+            .any(|(player, _)| !(&turrets).join().any(|turret| turret.tank == player));
+        if turretless_player {
+            let new_turrets: Vec<(Entity, Entity)> = (&entities, &named, !&turrets)
+                .join()
+                .filter(|(_, name, _)| name.name == TURRET_NODE_NAME)
+                .filter_map(|(entity, _, _)| {
+                    player_ancestor(entity, &parents, &players).map(|tank| (entity, tank))
+                })
+                .collect();
+            for (entity, tank) in new_turrets {
+                let _ = turrets.insert(entity, Turret::new(tank));
+            }
+        }
+
+        let dt = time.delta_seconds();
+        match preferences.control_scheme {
+            ControlScheme::Tank => {
+                let turn = controls.apply(
+                    input.axis_value("turret_rotation").unwrap_or_else(|| 0.0) as f32
+                );
+                for turret in (&mut turrets).join() {
+                    // Kept small, so it doesn't lose precision over a long game.
+                    turret.yaw = wrap_angle(turret.yaw + turn * TURN_RATE * dt);
+                }
+            }
+            ControlScheme::CameraRelative => {
+                let target = mouse_ground_position(
+                    &input,
+                    &screen_dimensions,
+                    &cameras,
+                    &transforms,
+                );
+                if let Some(target) = target {
+                    for turret in (&mut turrets).join() {
+                        if let Some(tank_transform) = transforms.get(turret.tank) {
+                            let to_target = target - tank_transform.translation();
+                            if to_target.x == 0.0 && to_target.z == 0.0 {
+                                continue;
+                            }
+                            let tank_yaw = yaw_of_direction(&heading(tank_transform));
+                            turret.yaw = wrap_angle(yaw_of_direction(&to_target) - tank_yaw);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (turret, transform) in (&turrets, &mut transforms).join() {
+            // The tank model is authored Z-up (its base node is rotated
+            //  to be Y-up), so the turret turns about its local Z axis.
+            transform.set_rotation_z_axis(-turret.yaw);
+        }
+    }
+}
@@ -23,10 +23,11 @@ impl<'a> System<'a> for UISystem {
     fn run(&mut self, data: Self::SystemData) {
         let (time, mut ui_text, fps_counter, finder) =
             data;
-        if let None = self.fps_display {
-            if let Some(fps_entity) = finder.find("fps_text") {
-                self.fps_display = Some(fps_entity);
-            }
+        // The fps text is re-created each time the level is (re)started.
+        let fps_display_is_stale = self.fps_display
+            .map_or(true, |fps_entity| ui_text.get(fps_entity).is_none());
+        if fps_display_is_stale {
+            self.fps_display = finder.find("fps_text");
         }
         if let Some(fps_entity) = self.fps_display {
             if let Some(fps_display) = ui_text.get_mut(fps_entity) {
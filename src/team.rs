@@ -0,0 +1,91 @@
+use std::fs::File;
+use std::path::Path;
+
+use amethyst::{
+    ecs::DenseVecStorage,
+    ecs::prelude::Component,
+    error::Error,
+    renderer::{palette::Srgba, resources::Tint},
+};
+use log::warn;
+use ron::de::from_reader as from_ron_reader;
+use serde::Deserialize;
+
+use crate::assets::AssetRegistry;
+use crate::replace_material::{MaterialRule, NamePattern};
+
+// The team an entity (e.g. a tank) belongs to.
+//
+// The TeamColourSystem gives the entity's nodes the team's colours.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Team {
+    /// Name of the team in the TeamPalette
+    pub name: String,
+}
+
+impl Team {
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Team { name: name.into() }
+    }
+}
+
+impl Component for Team {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// How a team's units are coloured.
+#[derive(Clone, Debug, Deserialize)]
+pub struct TeamColours {
+    pub name: String,
+    /// Name of a material (from the asset manifest) replacing the nodes' own
+    #[serde(default)]
+    pub material: Option<String>,
+    /// Colour multiplied with the nodes' material, as (red, green, blue, alpha);
+    ///  so teams can share one texture
+    #[serde(default)]
+    pub tint: Option<(f32, f32, f32, f32)>,
+}
+
+// Every team's colours, loaded from `assets/team/palette.ron`.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TeamPalette {
+    /// The nodes of a unit which take its team's colours
+    pub targets: Option<NamePattern>,
+    pub teams: Vec<TeamColours>,
+}
+
+impl TeamPalette {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<TeamPalette, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|_| {
+            Error::from_string(format!("Failed to open team palette {}", path.display()))
+        })?;
+        from_ron_reader(file).map_err(|err| {
+            Error::from_string(format!(
+                "Failed to parse team palette {}: {}", path.display(), err
+            ))
+        })
+    }
+
+    pub fn team(&self, name: &str) -> Option<&TeamColours> {
+        self.teams.iter().find(|team| team.name == name)
+    }
+
+    // The material rules giving a unit the team's colours.
+    pub fn rules(&self, team: &str, registry: &AssetRegistry) -> Vec<MaterialRule> {
+        let colours = match self.team(team) {
+            Some(colours) => colours,
+            None => {
+                warn!("unknown team: {}", team);
+                return Vec::new();
+            }
+        };
+
+        vec![MaterialRule {
+            name: self.targets.clone(),
+            original_nodes: None,
+            replacement: colours.material.as_ref().and_then(|name| registry.material(name)),
+            tint: colours.tint.map(|(r, g, b, a)| Tint(Srgba::new(r, g, b, a))),
+        }]
+    }
+}
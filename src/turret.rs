@@ -0,0 +1,28 @@
+use amethyst::{
+    ecs::DenseVecStorage,
+    ecs::prelude::{Component, Entity},
+};
+
+/// Name of the turret's node in the tank's glTF scene
+pub static TURRET_NODE_NAME: &str = "Turret";
+
+// The rotatable turret of a tank.
+//
+// Added by the TurretSystem to the turret node of a Player's glTF scene.
+#[derive(Clone, Debug)]
+pub struct Turret {
+    /// The tank (Player) which the turret belongs to
+    pub tank: Entity,
+    /// Rotation relative to the tank's heading, in radians (anti-clockwise from above)
+    pub yaw: f32,
+}
+
+impl Turret {
+    pub fn new(tank: Entity) -> Self {
+        Turret { tank, yaw: 0.0 }
+    }
+}
+
+impl Component for Turret {
+    type Storage = DenseVecStorage<Self>;
+}
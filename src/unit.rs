@@ -0,0 +1,276 @@
+// Units (e.g. tanks), defined by data files in `assets/unit/`,
+//  and spawned the same way for the player and enemies.
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::f32::consts::PI;
+use std::fs::File;
+use std::path::Path;
+
+use amethyst::{
+    core::{
+        transform::Transform,
+        Named,
+    },
+    ecs::DenseVecStorage,
+    ecs::prelude::{Component, Entity},
+    error::Error,
+    prelude::*,
+};
+use log::{error, warn};
+use ron::de::from_reader as from_ron_reader;
+use serde::Deserialize;
+
+use crate::animation::AnimationController;
+use crate::assets::{AssetDependencies, AssetManifest, AssetRegistry};
+use crate::collision::{Collider, ColliderDefinition};
+use crate::level::LevelEntity;
+use crate::replace_material::{MaterialRule, ReplaceMaterial, SerializedMaterialRule};
+use crate::spatial_grid::SpatialIndexed;
+use crate::team::Team;
+use crate::utils::log_gltf_info;
+
+fn default_speed() -> f32 {
+    2.0
+}
+
+fn default_turn_rate() -> f32 {
+    90.0
+}
+
+fn default_health() -> f32 {
+    100.0
+}
+
+// How fast a unit moves.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct MovementDefinition {
+    /// Units per second
+    #[serde(default = "default_speed")]
+    pub speed: f32,
+    /// Degrees per second
+    #[serde(default = "default_turn_rate")]
+    pub turn_rate: f32,
+}
+
+impl Default for MovementDefinition {
+    fn default() -> Self {
+        MovementDefinition {
+            speed: default_speed(),
+            turn_rate: default_turn_rate(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub struct WeaponDefinition {
+    /// Seconds between shots
+    pub cooldown: f32,
+    /// Damage dealt by each shot
+    pub damage: f32,
+}
+
+// A kind of unit, loaded from e.g. `assets/unit/tank.ron`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct UnitDefinition {
+    /// Name of the file the definition was loaded from, e.g. "tank"
+    #[serde(skip)]
+    pub name: String,
+    /// The unit's glTF scene, by its name in the asset manifest
+    pub mesh: String,
+    /// Material overrides for the unit's nodes (applied after its team's colours)
+    #[serde(default)]
+    pub materials: Vec<SerializedMaterialRule>,
+    #[serde(default)]
+    pub movement: MovementDefinition,
+    /// Units without a weapon can't fire
+    #[serde(default)]
+    pub weapon: Option<WeaponDefinition>,
+    #[serde(default = "default_health")]
+    pub health: f32,
+    /// Units without a collider don't collide
+    #[serde(default)]
+    pub collider: Option<ColliderDefinition>,
+}
+
+impl Default for UnitDefinition {
+    fn default() -> Self {
+        UnitDefinition {
+            name: String::new(),
+            mesh: String::new(),
+            materials: Vec::new(),
+            movement: MovementDefinition::default(),
+            weapon: None,
+            health: default_health(),
+            collider: None,
+        }
+    }
+}
+
+impl UnitDefinition {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<UnitDefinition, Error> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|_| {
+            Error::from_string(format!("Failed to open unit definition {}", path.display()))
+        })?;
+        let mut definition: UnitDefinition = from_ron_reader(file).map_err(|err| {
+            Error::from_string(format!(
+                "Failed to parse unit definition {}: {}", path.display(), err
+            ))
+        })?;
+        definition.name = path
+            .file_stem()
+            .map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+        Ok(definition)
+    }
+
+    // Load the named unit's definition from the assets directory's `unit/`.
+    pub fn load_named(assets_dir: &Path, name: &str) -> Result<UnitDefinition, Error> {
+        UnitDefinition::load(assets_dir.join("unit").join(format!("{}.ron", name)))
+    }
+
+    // Resolve a collider fitting the unit's mesh (`MeshBounds`) into a box.
+    pub fn resolve_collider(&mut self, manifest: &AssetManifest) -> Result<(), Error> {
+        let (name, mesh) = (&self.name, &self.mesh);
+        if let Some(collider) = &mut self.collider {
+            collider.resolve_mesh_bounds(manifest, mesh).map_err(|err| {
+                Error::from_string(format!("Unit '{}' has a bad collider: {}", name, err))
+            })?;
+        }
+        Ok(())
+    }
+
+    // Find the nodes of the unit's mesh which its material rules'
+    //  original materials and textures match.
+    pub fn resolve_material_originals(&mut self, manifest: &AssetManifest) -> Result<(), Error> {
+        let path = manifest.mesh_path(&self.mesh)?;
+        for rule in &mut self.materials {
+            rule.resolve_originals(&path)?;
+        }
+        Ok(())
+    }
+
+    // The assets (from the manifest) which spawning the unit needs.
+    pub fn dependencies(&self) -> AssetDependencies {
+        let mut dependencies = AssetDependencies::default();
+        dependencies.meshes.push(self.mesh.clone());
+        for rule in &self.materials {
+            dependencies.materials.extend(rule.replacement.iter().cloned());
+        }
+        dependencies
+    }
+}
+
+// A unit's movement stats, from its definition.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Movement {
+    pub speed: f32,
+    /// Radians per second
+    pub turn_rate: f32,
+}
+
+impl Component for Movement {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Weapon {
+    pub cooldown: f32,
+    pub damage: f32,
+    /// Seconds until the weapon can fire again
+    pub remaining: f32,
+}
+
+impl Component for Weapon {
+    type Storage = DenseVecStorage<Self>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Health { current: max, max }
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+impl Component for Health {
+    type Storage = DenseVecStorage<Self>;
+}
+
+// Resolve material rules against the loaded materials, skipping (and logging) bad ones.
+pub fn resolve_material_rules(
+    rules: &[SerializedMaterialRule],
+    registry: &AssetRegistry,
+) -> Vec<MaterialRule> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            rule.resolve(registry)
+                .map_err(|err| warn!("skipping material rule: {}", err))
+                .ok()
+        })
+        .collect()
+}
+
+// Spawn a unit, as part of the level.
+//
+// The unit's assets must already be loaded (see `UnitDefinition::dependencies`).
+pub fn spawn_unit(
+    world: &mut World,
+    definition: &UnitDefinition,
+    transform: Transform,
+    team: Team,
+) -> Entity {
+    let registry = world
+        .try_fetch::<AssetRegistry>()
+        .map(|registry| (*registry).clone())
+        .unwrap_or_default();
+
+    let rules = resolve_material_rules(&definition.materials, &registry);
+
+    let mesh = registry.mesh(&definition.mesh);
+    match &mesh {
+        Some(mesh) => log_gltf_info(world, mesh),
+        None => error!("Mesh '{}' isn't loaded", definition.mesh),
+    }
+
+    let mut builder = world
+        .create_entity()
+        .with(transform)
+        .with(Named { name: Cow::Owned(definition.name.clone()) })
+        .with(team)
+        .with(LevelEntity)
+        .with(SpatialIndexed)
+        .with(ReplaceMaterial::new(rules))
+        .with(AnimationController::new(registry.animation_clips(&definition.mesh)))
+        .with(Movement {
+            speed: definition.movement.speed,
+            turn_rate: definition.movement.turn_rate * PI / 180.0,
+        })
+        .with(Health::new(definition.health));
+    if let Some(weapon) = &definition.weapon {
+        builder = builder.with(Weapon {
+            cooldown: weapon.cooldown,
+            damage: weapon.damage,
+            remaining: 0.0,
+        });
+    }
+    if let Some(collider) = &definition.collider {
+        match Collider::try_from(collider) {
+            Ok(collider) => builder = builder.with(collider),
+            Err(err) => error!("Unit '{}' won't collide: {}", definition.name, err),
+        }
+    }
+    if let Some(mesh) = mesh {
+        builder = builder.with(mesh);
+    }
+    builder.build()
+}
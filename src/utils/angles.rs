@@ -0,0 +1,49 @@
+use std::f32::consts::PI;
+
+use amethyst::core::transform::Transform;
+use nalgebra::Vector3;
+
+// The direction an entity is facing (its local +Z), in its parent's space.
+pub fn heading(transform: &Transform) -> Vector3<f32> {
+    transform.rotation() * Vector3::z()
+}
+
+// Rotation about the Y axis of a direction on the ground plane,
+//  where +Z is 0 and +X is PI / 2.
+pub fn yaw_of_direction(direction: &Vector3<f32>) -> f32 {
+    direction.x.atan2(direction.z)
+}
+
+// Wrap an angle into (-PI, PI].
+pub fn wrap_angle(angle: f32) -> f32 {
+    PI - (PI - angle).rem_euclid(2.0 * PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) -> () {
+        assert!((actual - expected).abs() < 1e-4, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn angles_are_wrapped_into_a_half_open_range() {
+        assert_close(wrap_angle(0.5), 0.5);
+        assert_close(wrap_angle(-0.5), -0.5);
+        assert_close(wrap_angle(PI), PI);
+        assert_close(wrap_angle(-PI), PI);
+        assert_close(wrap_angle(1.5 * PI), -0.5 * PI);
+        assert_close(wrap_angle(-1.5 * PI), 0.5 * PI);
+        assert_close(wrap_angle(7.0 * PI + 0.25), -PI + 0.25);
+    }
+
+    #[test]
+    fn turning_for_a_long_time_stays_in_range() {
+        let mut yaw = 0.0;
+        for _ in 0..1_000_000 {
+            yaw = wrap_angle(yaw + 0.0261);
+            assert!(yaw > -PI && yaw <= PI, "{} out of range", yaw);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use amethyst::{
+    core::ParentHierarchy,
+    ecs::prelude::{Entity, Join},
+    prelude::*,
+};
+use log::error;
+
+// Delete an entity, along with all of its descendants.
+//
+// e.g. deleting the root of a UI created with UiCreator
+//  deletes all of the UI's widgets.
+pub fn delete_hierarchy(world: &mut World, root: Entity) -> () {
+    let mut to_delete: Vec<Entity> = {
+        let hierarchy = world.read_resource::<ParentHierarchy>();
+        let entities = world.entities();
+        let descendants = hierarchy.all_children(root);
+        (&*entities, &descendants)
+            .join()
+            .map(|(entity, _)| entity)
+            .collect()
+    };
+    to_delete.push(root);
+
+    if let Err(err) = world.delete_entities(&to_delete) {
+        error!("Failed deleting entity hierarchy: {}", err);
+    }
+}
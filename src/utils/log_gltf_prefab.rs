@@ -0,0 +1,41 @@
+use amethyst::{
+    assets::{AssetStorage, Handle},
+    prelude::*,
+};
+use amethyst_gltf::{GltfPrefab, GltfSceneAsset};
+use log::{debug, log_enabled, warn, Level};
+
+pub fn log_info_about_gltf_prefab(gltf_prefab: &mut GltfPrefab) -> () {
+    debug!(
+        "GLTF node: name = {}, has transform = {}, has material = {}",
+        gltf_prefab.name.as_ref().map_or("(none)", |name| name.name.as_ref()),
+        gltf_prefab.transform.is_some(),
+        gltf_prefab.material.is_some(),
+    );
+}
+
+// Log (at debug level) the nodes of a loaded glTF scene.
+pub fn log_gltf_info(
+    world: &mut World,
+    gltf_asset_handle: &Handle<GltfSceneAsset>
+) -> () {
+    if !log_enabled!(Level::Debug) {
+        return;
+    }
+    let asset_storage: &mut AssetStorage<GltfSceneAsset> =
+        &mut world.write_resource();
+    let gltf_asset: Option<&mut GltfSceneAsset> =
+        asset_storage.get_mut(gltf_asset_handle);
+    if let Some(gltf_asset) = gltf_asset {
+        let len = gltf_asset.len();
+        debug!("gltf_asset (Prefab<GltfPrefab>) has length = {}", len);
+
+        for idx in 0..len {
+            let gltf_prefab = gltf_asset.data_or_default(idx);
+
+            log_info_about_gltf_prefab(gltf_prefab);
+        }
+    } else {
+        warn!("Can't find GltfSceneAsset for given handle.");
+    }
+}
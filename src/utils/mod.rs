@@ -1,3 +1,9 @@
-mod print_gltf_prefab;
+mod angles;
+mod hierarchy;
+mod log_gltf_prefab;
+mod noise;
 
-pub use self::print_gltf_prefab::print_gltf_info;
+pub use self::angles::{heading, wrap_angle, yaw_of_direction};
+pub use self::hierarchy::delete_hierarchy;
+pub use self::log_gltf_prefab::log_gltf_info;
+pub use self::noise::value_noise;
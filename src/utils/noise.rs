@@ -0,0 +1,97 @@
+// Deterministic 1D value noise.
+//
+// The same seed and position always give the same value,
+//  so effects using it (e.g. camera shake) are reproducible.
+
+fn hash(seed: u32, x: i32) -> u32 {
+    let mut h = seed ^ (x as u32).wrapping_mul(0x27d4_eb2d);
+    h = (h ^ (h >> 15)).wrapping_mul(0x2c1b_3c6d);
+    h = (h ^ (h >> 12)).wrapping_mul(0x297a_2d39);
+    h ^ (h >> 15)
+}
+
+// Random value in -1.0..=1.0 at an integer position.
+fn lattice_value(seed: u32, x: i32) -> f32 {
+    hash(seed, x) as f32 / std::u32::MAX as f32 * 2.0 - 1.0
+}
+
+// Smoothly varying noise in -1.0..=1.0.
+pub fn value_noise(seed: u32, t: f32) -> f32 {
+    let x0 = t.floor();
+    let fraction = t - x0;
+    let smoothed = fraction * fraction * (3.0 - 2.0 * fraction);
+
+    let a = lattice_value(seed, x0 as i32);
+    let b = lattice_value(seed, x0 as i32 + 1);
+    a + (b - a) * smoothed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEEDS: [u32; 4] = [0, 1, 42, 0xdead_beef];
+
+    // Positions from -50.0 to 50.0, in steps of 0.01.
+    fn positions() -> impl Iterator<Item = f32> {
+        (-5000..=5000).map(|step| step as f32 * 0.01)
+    }
+
+    // Changing the hash or the interpolation changes these.
+    #[test]
+    fn values_match_known_ones() {
+        let known = [
+            (0, 0.0, -1.0),
+            (0, 0.5, -0.381_800_95),
+            (1, 2.25, 0.189_285_17),
+            (42, -3.75, 0.606_606_5),
+            (0xdead_beef, 10.6, -0.212_343_35),
+        ];
+        for &(seed, t, expected) in &known {
+            let value = value_noise(seed, t);
+            assert!((value - expected).abs() < 1e-6, "value_noise({}, {}) = {}", seed, t, value);
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_noise() {
+        let differs = positions().any(|t| value_noise(1, t) != value_noise(2, t));
+        assert!(differs);
+    }
+
+    #[test]
+    fn values_stay_in_range() {
+        for &seed in &SEEDS {
+            for t in positions() {
+                let value = value_noise(seed, t);
+                assert!((-1.0..=1.0).contains(&value), "value_noise({}, {}) = {}", seed, t, value);
+            }
+        }
+    }
+
+    #[test]
+    fn noise_is_continuous_across_integers() {
+        const EPSILON: f32 = 1e-3;
+        for &seed in &SEEDS {
+            for n in -50..=50 {
+                let t = n as f32;
+                let at = value_noise(seed, t);
+                assert_eq!(at, lattice_value(seed, n));
+                // The smoothing flattens the noise out at integers,
+                //  so nearby values differ by much less than EPSILON.
+                for &nearby in &[t - EPSILON, t + EPSILON] {
+                    let difference = (value_noise(seed, nearby) - at).abs();
+                    assert!(difference < 1e-4, "jump of {} at {} (seed {})", difference, t, seed);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn noise_varies() {
+        for &seed in &SEEDS {
+            let start = value_noise(seed, 0.0);
+            assert!(positions().any(|t| (value_noise(seed, t) - start).abs() > 0.1));
+        }
+    }
+}
@@ -0,0 +1,418 @@
+// Checks the game's data files offline, without opening a window (or needing a GPU).
+//
+// Parses the asset manifest, levels (and their units), team palette, material rules, UI,
+//  sprite sheets, sprite grids, glTF files and input config,
+//  and checks the references between them.
+//
+// Prints a report, and exits with a non-zero status if there were any problems.
+
+// The game's modules are shared with the game binaries,
+//  but only their loading code is used here.
+#![allow(dead_code)]
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use amethyst::{
+    input::{Bindings, StringBindings},
+    prelude::Config,
+    renderer::sprite::Sprites,
+    ui::UiWidget,
+    utils::application_root_dir,
+    window::DisplayConfig,
+};
+use regex::Regex;
+use serde::de::DeserializeOwned;
+
+use crate::assets::{AssetKind, AssetManifest, TextureSource};
+use crate::controls::ControlsConfig;
+use crate::level::Level;
+use crate::replace_material::{
+    load_material_rules,
+    NamePattern,
+    SerializedMaterialRule,
+};
+use crate::sprite_grid::read_sprite_grid;
+use crate::team::TeamPalette;
+
+mod animation;
+mod assets;
+mod camera;
+mod collision;
+mod controls;
+mod level;
+mod player;
+mod replace_material;
+mod spatial_grid;
+mod sprite_grid;
+mod team;
+mod unit;
+mod utils;
+
+const USAGE: &str = "\
+Usage: validate-assets [options]
+
+Options:
+    --assets-dir <path>     Assets directory (default: assets)
+    --resources-dir <path>  Resources directory (default: resources)
+    --help                  Print this message";
+
+// Quoted paths of the fonts, images and other files a UI file refers to.
+const UI_ASSET_PATH_PATTERN: &str = r#""([^"]+\.(ttf|otf|png|jpg|ron|gltf))""#;
+
+#[derive(Default)]
+struct Report {
+    checked: usize,
+    problems: Vec<String>,
+}
+
+impl Report {
+    // Record the result of checking something, e.g. a file.
+    fn check<T>(&mut self, what: &str, result: Result<T, String>) -> Option<T> {
+        self.checked += 1;
+        match result {
+            Ok(value) => {
+                println!("ok    {}", what);
+                Some(value)
+            }
+            Err(err) => {
+                println!("FAIL  {}: {}", what, err);
+                self.problems.push(format!("{}: {}", what, err));
+                None
+            }
+        }
+    }
+}
+
+fn parse_ron<T: DeserializeOwned>(path: &Path) -> Result<T, String> {
+    let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    ron::de::from_str(&contents).map_err(|err| err.to_string())
+}
+
+fn ron_files_in(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().map_or(false, |ext| ext == "ron"))
+                .collect()
+        })
+        .unwrap_or_else(|_| Vec::new());
+    paths.sort();
+    paths
+}
+
+fn exists(assets_dir: &Path, path: &str) -> Result<(), String> {
+    if assets_dir.join(path).is_file() {
+        Ok(())
+    } else {
+        Err(format!("missing file {}", path))
+    }
+}
+
+// The names of a glTF file's nodes, checking that its buffers and images load.
+fn gltf_node_names(path: &Path) -> Result<BTreeSet<String>, String> {
+    let (document, _buffers, _images) = gltf::import(path).map_err(|err| err.to_string())?;
+    Ok(document
+        .nodes()
+        .filter_map(|node| node.name().map(String::from))
+        .collect())
+}
+
+fn check_pattern(
+    pattern: &NamePattern,
+    node_names: &BTreeSet<String>,
+) -> Result<(), String> {
+    if node_names.iter().any(|name| pattern.matches(name)) {
+        Ok(())
+    } else {
+        Err(format!("name pattern {:?} doesn't match any glTF node", pattern))
+    }
+}
+
+fn check_material_rule(
+    rule: &SerializedMaterialRule,
+    manifest: &AssetManifest,
+    node_names: &BTreeSet<String>,
+) -> Result<(), String> {
+    if let Some(material) = &rule.replacement {
+        if !manifest.materials.contains_key(material) {
+            return Err(format!("unknown material '{}'", material));
+        }
+    }
+    if let Some(pattern) = &rule.name {
+        check_pattern(pattern, node_names)?;
+    }
+    if rule.original_nodes.as_ref().map_or(false, |nodes| nodes.is_empty()) {
+        return Err(format!(
+            "original material {:?} / texture {:?} doesn't match any glTF node",
+            rule.original_material, rule.original_texture,
+        ));
+    }
+    Ok(())
+}
+
+fn check_resources(report: &mut Report, resources_dir: &Path) -> () {
+    let display_config_path = resources_dir.join("display_config.ron");
+    report.check(
+        &display_config_path.display().to_string(),
+        DisplayConfig::load(&display_config_path).map_err(|err| err.to_string()),
+    );
+
+    let input_path = resources_dir.join("input.ron");
+    report.check(
+        &input_path.display().to_string(),
+        Bindings::<StringBindings>::load(&input_path).map_err(|err| err.to_string()),
+    );
+
+    let controls_path = resources_dir.join("controls.ron");
+    report.check(
+        &controls_path.display().to_string(),
+        ControlsConfig::load(&controls_path).map_err(|err| err.to_string()),
+    );
+}
+
+fn check_ui(report: &mut Report, assets_dir: &Path) -> () {
+    let asset_path_regex = Regex::new(UI_ASSET_PATH_PATTERN).expect("valid regex");
+    for path in ron_files_in(&assets_dir.join("ui")) {
+        let result = parse_ron::<UiWidget>(&path).and_then(|_| {
+            let contents = fs::read_to_string(&path).map_err(|err| err.to_string())?;
+            for captures in asset_path_regex.captures_iter(&contents) {
+                exists(assets_dir, &captures[1])?;
+            }
+            Ok(())
+        });
+        report.check(&path.display().to_string(), result);
+    }
+}
+
+// Check the files listed in the manifest.
+//
+// Returns the node names of each mesh which loaded.
+fn check_manifest_assets(
+    report: &mut Report,
+    assets_dir: &Path,
+    manifest: &AssetManifest,
+) -> BTreeMap<String, BTreeSet<String>> {
+    for (name, path) in &manifest.textures {
+        report.check(&format!("texture '{}'", name), exists(assets_dir, path));
+    }
+
+    for (name, material) in &manifest.materials {
+        let textures = Some(&material.albedo).into_iter().chain(material.emission.iter());
+        let result = textures
+            .filter_map(|source| match source {
+                TextureSource::Texture(texture) => Some(texture),
+                TextureSource::Colour(_) => None,
+            })
+            .find(|texture| !manifest.textures.contains_key(*texture))
+            .map_or(Ok(()), |texture| Err(format!("unknown texture '{}'", texture)));
+        report.check(&format!("material '{}'", name), result);
+    }
+
+    // `None` if the sprite sheet's grid doesn't say how many sprites it has.
+    let mut sprite_counts: BTreeMap<String, Option<usize>> = BTreeMap::new();
+    for (name, sprite_sheet) in &manifest.sprite_sheets {
+        let result = exists(assets_dir, &sprite_sheet.path)
+            .and_then(|_| parse_ron::<Sprites>(&assets_dir.join(&sprite_sheet.path)))
+            .map(|sprites| match sprites {
+                Sprites::List(list) => Some(list.sprites.len()),
+                Sprites::Grid(grid) => grid.sprite_count
+                    .or_else(|| grid.rows.map(|rows| rows * grid.columns))
+                    .map(|count| count as usize),
+            });
+        if let Some(count) = report.check(&format!("sprite sheet '{}'", name), result) {
+            sprite_counts.insert(sprite_sheet.path.clone(), count);
+        }
+    }
+
+    for (name, grid) in &manifest.grids {
+        let result = exists(assets_dir, &grid.path)
+            .and_then(|_| {
+                read_sprite_grid(assets_dir.join(&grid.path)).map_err(|err| err.to_string())
+            })
+            .and_then(|sprite_grid| {
+                let num_cols = sprite_grid.grid.first().map_or(0, |row| row.len());
+                if sprite_grid.grid.iter().any(|row| row.len() != num_cols) {
+                    return Err(String::from("rows have different lengths"));
+                }
+                let num_sprites = sprite_counts
+                    .get(&sprite_grid.spritesheet_path)
+                    .ok_or_else(|| format!(
+                        "spritesheet_path {} isn't a (valid) sprite sheet in the manifest",
+                        sprite_grid.spritesheet_path,
+                    ))?;
+                let max_index = sprite_grid.grid.iter().flatten().max().cloned().unwrap_or(0);
+                match num_sprites {
+                    Some(num_sprites) if max_index >= *num_sprites => Err(format!(
+                        "sprite index {} is out of range; the sprite sheet has {} sprites",
+                        max_index, num_sprites,
+                    )),
+                    _ => Ok(()),
+                }
+            });
+        report.check(&format!("grid '{}'", name), result);
+    }
+
+    let mut mesh_nodes = BTreeMap::new();
+    for (name, path) in &manifest.meshes {
+        let result = exists(assets_dir, path)
+            .and_then(|_| gltf_node_names(&assets_dir.join(path)));
+        if let Some(node_names) = report.check(&format!("mesh '{}'", name), result) {
+            mesh_nodes.insert(name.clone(), node_names);
+        }
+    }
+    mesh_nodes
+}
+
+fn check_team_palette(
+    report: &mut Report,
+    assets_dir: &Path,
+    manifest: &AssetManifest,
+    mesh_nodes: &BTreeMap<String, BTreeSet<String>>,
+) -> Option<TeamPalette> {
+    let path = assets_dir.join("team").join("palette.ron");
+    let all_nodes: BTreeSet<String> = mesh_nodes.values().flatten().cloned().collect();
+
+    let result = TeamPalette::load(&path)
+        .map_err(|err| err.to_string())
+        .and_then(|palette| {
+            for team in &palette.teams {
+                if let Some(material) = &team.material {
+                    if !manifest.materials.contains_key(material) {
+                        return Err(format!(
+                            "team '{}' has unknown material '{}'", team.name, material
+                        ));
+                    }
+                }
+            }
+            if let Some(targets) = &palette.targets {
+                check_pattern(targets, &all_nodes)?;
+            }
+            Ok(palette)
+        });
+    report.check(&path.display().to_string(), result)
+}
+
+fn check_levels(
+    report: &mut Report,
+    assets_dir: &Path,
+    manifest: &AssetManifest,
+    mesh_nodes: &BTreeMap<String, BTreeSet<String>>,
+    palette: Option<&TeamPalette>,
+) -> () {
+    // If the palette failed to load (already reported), check the levels without team colours.
+    let default_palette = TeamPalette::default();
+    for path in ron_files_in(&assets_dir.join("level")) {
+        let result = Level::load(&path, assets_dir, manifest, palette.unwrap_or(&default_palette))
+            .map_err(|err| err.to_string())
+            .and_then(|level| {
+                // The nodes of the meshes the level uses.
+                let node_names: BTreeSet<String> = level.assets
+                    .iter()
+                    .filter(|key| key.kind == AssetKind::Mesh)
+                    .filter_map(|key| mesh_nodes.get(&key.name))
+                    .flatten()
+                    .cloned()
+                    .collect();
+                let units = Some(&level.player_unit)
+                    .into_iter()
+                    .chain(level.enemies.iter().map(|enemy| &enemy.unit));
+                let unit_rules = units.flat_map(|unit| unit.materials.iter());
+                for rule in level.player_material_rules.iter().chain(unit_rules) {
+                    check_material_rule(rule, manifest, &node_names)?;
+                }
+
+                if let Some(palette) = palette {
+                    let teams = Some(&level.player_team)
+                        .into_iter()
+                        .chain(level.enemies.iter().map(|enemy| &enemy.team));
+                    for team in teams {
+                        if palette.team(team).is_none() {
+                            return Err(format!("unknown team '{}'", team));
+                        }
+                    }
+                }
+                Ok(())
+            });
+        report.check(&path.display().to_string(), result);
+    }
+}
+
+// Check every material rules file, including ones which no level uses.
+//
+// The rules' name patterns and original materials are checked against nodes by check_levels.
+fn check_material_rules(report: &mut Report, assets_dir: &Path, manifest: &AssetManifest) -> () {
+    let no_nodes = BTreeSet::new();
+    for path in ron_files_in(&assets_dir.join("material")) {
+        let result = load_material_rules(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|rules| {
+                rules
+                    .iter()
+                    .map(|rule| SerializedMaterialRule {
+                        name: None,
+                        original_nodes: None,
+                        ..rule.clone()
+                    })
+                    .map(|rule| check_material_rule(&rule, manifest, &no_nodes))
+                    .collect::<Result<Vec<()>, String>>()
+            });
+        report.check(&path.display().to_string(), result);
+    }
+}
+
+fn main() -> () {
+    let mut assets_dir = None;
+    let mut resources_dir = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--assets-dir" => assets_dir = args.next().map(PathBuf::from),
+            "--resources-dir" => resources_dir = args.next().map(PathBuf::from),
+            "--help" | "-h" => {
+                println!("{}", USAGE);
+                return;
+            }
+            other => {
+                eprintln!("unknown option '{}'\n\n{}", other, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let app_root = application_root_dir().unwrap_or_else(|_| PathBuf::from("."));
+    let assets_dir = assets_dir.unwrap_or_else(|| app_root.join("assets"));
+    let resources_dir = resources_dir.unwrap_or_else(|| app_root.join("resources"));
+
+    let mut report = Report::default();
+
+    check_resources(&mut report, &resources_dir);
+    check_ui(&mut report, &assets_dir);
+
+    let manifest_path = assets_dir.join("manifest.ron");
+    let manifest = report.check(
+        &manifest_path.display().to_string(),
+        AssetManifest::load(&manifest_path).map_err(|err| err.to_string()),
+    );
+    if let Some(manifest) = manifest {
+        let mesh_nodes = check_manifest_assets(&mut report, &assets_dir, &manifest);
+        let palette = check_team_palette(&mut report, &assets_dir, &manifest, &mesh_nodes);
+        check_material_rules(&mut report, &assets_dir, &manifest);
+        check_levels(&mut report, &assets_dir, &manifest, &mesh_nodes, palette.as_ref());
+    }
+
+    println!();
+    if report.problems.is_empty() {
+        println!("Checked {} files and assets, no problems found.", report.checked);
+    } else {
+        println!("Checked {} files and assets, found {} problems:",
+                 report.checked,
+                 report.problems.len());
+        for problem in &report.problems {
+            println!("- {}", problem);
+        }
+        std::process::exit(1);
+    }
+}